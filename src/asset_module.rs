@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use crate::{hash_content, Asset, AssetFilename, ComplierOptions, Content, Module, ParseModuleArgs, SourceType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+  Resource,
+  Inline,
+  Source,
+}
+
+/// Built-in module for `SourceType::Asset*`.
+#[derive(Debug)]
+pub struct AssetModule {
+  pub uri: String,
+  pub kind: AssetKind,
+  pub content: Vec<u8>,
+  /// Output filename, only set for `AssetKind::Resource`.
+  pub filename: Option<String>,
+  mime_type: &'static str,
+  public_path: String,
+}
+
+impl AssetModule {
+  pub fn parse(
+    args: ParseModuleArgs,
+    source_type: SourceType,
+    options: &ComplierOptions,
+  ) -> anyhow::Result<Self> {
+    let content = args.source.as_bytes().to_vec();
+    let asset_options = &options.module.asset;
+    let kind = match source_type {
+      SourceType::AssetResource => AssetKind::Resource,
+      SourceType::AssetInline => AssetKind::Inline,
+      SourceType::AssetSource => AssetKind::Source,
+      SourceType::Asset if content.len() <= asset_options.data_url_max_size => AssetKind::Inline,
+      SourceType::Asset => AssetKind::Resource,
+      _ => anyhow::bail!("{:?} of {} is not an asset type", source_type, args.uri),
+    };
+    let filename = matches!(kind, AssetKind::Resource)
+      .then(|| asset_filename(&asset_options.filename, args.resource.path, &content));
+    Ok(Self {
+      uri: args.uri.to_string(),
+      kind,
+      content,
+      filename,
+      mime_type: mime_type(args.resource.path),
+      public_path: options.output.public_path.clone(),
+    })
+  }
+
+  fn data_uri(&self) -> String {
+    format!(
+      "data:{};base64,{}",
+      self.mime_type,
+      base64::encode(&self.content)
+    )
+  }
+}
+
+impl Module for AssetModule {
+  fn render(&self) -> String {
+    let exported = match self.kind {
+      AssetKind::Source => String::from_utf8_lossy(&self.content).to_string(),
+      _ => self.url().unwrap_or_default(),
+    };
+    format!("module.exports = {};", serde_json::Value::String(exported))
+  }
+
+  fn url(&self) -> Option<String> {
+    match self.kind {
+      AssetKind::Resource => Some(format!(
+        "{}{}",
+        self.public_path,
+        self.filename.as_deref().unwrap_or_default()
+      )),
+      AssetKind::Inline => Some(self.data_uri()),
+      AssetKind::Source => None,
+    }
+  }
+
+  fn assets(&self) -> Vec<Asset> {
+    match &self.filename {
+      Some(filename) => vec![Asset {
+        rendered: Content::Buffer(self.content.clone()),
+        filename: AssetFilename::Static(filename.clone()),
+      }],
+      None => vec![],
+    }
+  }
+}
+
+fn asset_filename(template: &str, path: &str, content: &[u8]) -> String {
+  let path = Path::new(path);
+  let name = path
+    .file_stem()
+    .and_then(|name| name.to_str())
+    .unwrap_or_default();
+  let ext = path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .map(|ext| format!(".{}", ext))
+    .unwrap_or_default();
+  template
+    .replace("[name]", name)
+    .replace("[hash]", &hash_content(content))
+    .replace("[ext]", &ext)
+}
+
+fn mime_type(path: &str) -> &'static str {
+  let ext = Path::new(path)
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .unwrap_or_default();
+  match ext {
+    "png" => "image/png",
+    "jpg" | "jpeg" => "image/jpeg",
+    "gif" => "image/gif",
+    "webp" => "image/webp",
+    "avif" => "image/avif",
+    "ico" => "image/x-icon",
+    "bmp" => "image/bmp",
+    "svg" => "image/svg+xml",
+    "woff" => "font/woff",
+    "woff2" => "font/woff2",
+    "ttf" => "font/ttf",
+    "otf" => "font/otf",
+    "eot" => "application/vnd.ms-fontobject",
+    "txt" => "text/plain",
+    _ => "application/octet-stream",
+  }
+}
@@ -1,12 +1,14 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
-use crate::{ModuleGraph, ModuleGraphModule};
+use crate::{ConcatenatedModule, HoistedImport, ModuleGraph, ModuleGraphModule};
 
 #[derive(Debug)]
 pub struct Chunk {
   pub id: String,
   pub(crate) entry_uri: String,
-  pub(crate) module_uris: HashSet<String>,
+  pub(crate) module_uris: BTreeSet<String>,
+  /// Scopes merged by `concatenate_modules`, rendered in place of their modules.
+  pub concatenated_modules: Vec<ConcatenatedModule>,
   kind: ChunkKind,
 }
 
@@ -16,10 +18,56 @@ impl Chunk {
       id,
       entry_uri,
       module_uris: Default::default(),
+      concatenated_modules: vec![],
       kind,
     }
   }
 
+  /// Whether the module is merged into a concatenated scope of the chunk, without
+  /// being its root.
+  pub fn is_concatenated_inner(&self, uri: &str) -> bool {
+    self
+      .concatenated_modules
+      .iter()
+      .any(|concatenated| concatenated.root != uri && concatenated.modules.iter().any(|m| m == uri))
+  }
+
+  /// Code of each module of the chunk in evaluation order, keyed by uri, for the
+  /// module wrappers of a js chunk. A concatenated scope is rendered in place of its
+  /// root, the other modules of the scope are left out.
+  pub fn render_js_modules<'a>(&self, module_graph: &'a ModuleGraph) -> Vec<(&'a str, String)> {
+    self
+      .ordered_module(module_graph)
+      .into_iter()
+      .filter(|module| !self.is_concatenated_inner(&module.uri))
+      .map(|module| {
+        let code = match self
+          .concatenated_modules
+          .iter()
+          .find(|concatenated| concatenated.root == module.uri)
+        {
+          Some(concatenated) => concatenated.render(module_graph),
+          None => module.render_js(),
+        };
+        (module.uri.as_str(), code)
+      })
+      .collect()
+  }
+
+  /// Imports of the modules of the chunk to place at the top level of a js chunk,
+  /// see `Module::hoisted_import`.
+  pub fn hoisted_imports(&self, module_graph: &ModuleGraph) -> Vec<HoistedImport> {
+    let mut imports: Vec<HoistedImport> = vec![];
+    for module in self.ordered_module(module_graph) {
+      if let Some(import) = module.module.hoisted_import() {
+        if !imports.contains(&import) {
+          imports.push(import);
+        }
+      }
+    }
+    imports
+  }
+
   pub fn ordered_module<'a>(&self, module_graph: &'a ModuleGraph) -> Vec<&'a ModuleGraphModule> {
     let mut order = self
       .module_uris
@@ -28,7 +76,38 @@ impl Chunk {
       .collect::<Vec<_>>();
     order.sort_by_key(|m| m.exec_order);
     order
-  } 
+  }
+
+  /// Modules of the chunk in the order they are imported starting from the entry of
+  /// the chunk, a module comes after all of its dependencies.
+  pub fn import_ordered_module<'a>(&self, module_graph: &'a ModuleGraph) -> Vec<&'a ModuleGraphModule> {
+    let mut order = vec![];
+    let mut visited = HashSet::new();
+    let mut stack = vec![(self.entry_uri.as_str(), false)];
+    while let Some((uri, exiting)) = stack.pop() {
+      let module = match module_graph.module_by_uri(uri) {
+        Some(module) => module,
+        None => continue,
+      };
+      if exiting {
+        if self.module_uris.contains(uri) {
+          order.push(module);
+        }
+        continue;
+      }
+      if !visited.insert(uri) {
+        continue;
+      }
+      stack.push((uri, true));
+      module
+        .depended_modules(module_graph)
+        .into_iter()
+        .rev()
+        .filter(|dep| !visited.contains(dep.uri.as_str()))
+        .for_each(|dep| stack.push((dep.uri.as_str(), false)));
+    }
+    order
+  }
 }
 
 #[derive(Debug)]
@@ -1,9 +1,16 @@
+use std::collections::BTreeSet;
+
+use indexmap::IndexMap;
+
 use crate::chunk::Chunk;
 
 #[derive(Debug, Default)]
 pub struct ChunkGraph {
-  id_to_chunk: hashbrown::HashMap<String, Chunk>,
+  /// Chunks in the order they are created.
+  id_to_chunk: IndexMap<String, Chunk>,
   split_module_point_uri_to_chunk_id: hashbrown::HashMap<String, String>,
+  /// `(from, to)` ids of chunks where a module of `from` depends on the entry of `to`.
+  relations: BTreeSet<(String, String)>,
 }
 
 impl ChunkGraph {
@@ -23,10 +30,13 @@ impl ChunkGraph {
     }
 
     pub fn remove_by_id(&mut self, id: &str) -> Option<Chunk> {
-        let chunk = self.id_to_chunk.remove(id)?;
+        let chunk = self.id_to_chunk.shift_remove(id)?;
         self
           .split_module_point_uri_to_chunk_id
           .remove(&chunk.entry_uri);
+        self
+          .relations
+          .retain(|(from, to)| from != id && to != id);
         Some(chunk)
     }
 
@@ -43,7 +53,18 @@ impl ChunkGraph {
         self.id_to_chunk.get(chunk_id)
     }
 
-    pub fn id_to_chunk(&self) -> &hashbrown::HashMap<String, Chunk> {
+    pub fn add_relation(&mut self, from: String, to: String) {
+        self.relations.insert((from, to));
+    }
+
+    pub fn relations(&self) -> impl Iterator<Item = (&str, &str)> {
+        self
+          .relations
+          .iter()
+          .map(|(from, to)| (from.as_str(), to.as_str()))
+    }
+
+    pub fn id_to_chunk(&self) -> &IndexMap<String, Chunk> {
         &self.id_to_chunk
     }
 }
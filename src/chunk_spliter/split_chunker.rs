@@ -1,5 +1,5 @@
-use crate::{Compilation, ChunkIdAlgo, ModuleGraph, ext_by_module_uri, ChunkKind, Chunk};
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::{Compilation, ChunkIdAlgo, ModuleGraph, ext_by_module_uri, ChunkKind, Chunk, DroppedModule, ModuleGraphModule};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 pub fn split_code(compilation: &mut Compilation) {
 
@@ -43,7 +43,7 @@ pub fn split_code(compilation: &mut Compilation) {
 
   }
 
-  let mut mod_to_chunk_id: HashMap<&str, HashSet<&str>> = Default::default();
+  let mut mod_to_chunk_id: BTreeMap<&str, BTreeSet<&str>> = Default::default();
   for entry in &entries {
     let chunk_id = &chunk_id_by_entry_module_uri[*entry];
     let mut queue = [*entry].into_iter().collect::<VecDeque<_>>();
@@ -65,8 +65,6 @@ pub fn split_code(compilation: &mut Compilation) {
               let dep_module_uri = dep_module.uri.as_str();
               queue.push_back(dep_module_uri);
             });
-        } else {
-          todo!("circular dependency");
         }
     }
   }
@@ -88,6 +86,7 @@ pub fn split_code(compilation: &mut Compilation) {
     });
   println!("mod_to_chunk_id: {:#?}", mod_to_chunk_id);
 
+  let mut dropped_modules: HashMap<&str, DroppedModule> = HashMap::new();
   for entry in &entries {
     let mut queue = [*entry].into_iter().collect::<VecDeque<_>>();
     let mut visited = HashSet::new();
@@ -98,7 +97,21 @@ pub fn split_code(compilation: &mut Compilation) {
       if !visited.contains(module_uri) {
         visited.insert(module_uri);
 
-        let belong_to_chunks: &HashSet<&str> = &mod_to_chunk_id[module_uri];
+        if !module.included {
+          dropped_modules
+            .entry(module_uri)
+            .or_insert_with(|| DroppedModule {
+              uri: module_uri.to_string(),
+              reason: dropped_reason(module),
+            });
+          module
+            .depended_modules(module_graph)
+            .into_iter()
+            .for_each(|dep_module| queue.push_back(&dep_module.uri));
+          continue;
+        }
+
+        let belong_to_chunks: &BTreeSet<&str> = &mod_to_chunk_id[module_uri];
         println!(
           "[module {:?}]: belong to chunks {:?}",
           module_uri, belong_to_chunks
@@ -142,6 +155,13 @@ pub fn split_code(compilation: &mut Compilation) {
     }
   }
 
+  for (from, to, _) in chunk_relation_graph2.all_edges() {
+    chunk_graph.add_relation(from.to_string(), to.to_string());
+  }
+
+  let mut dropped_modules = dropped_modules.into_values().collect::<Vec<_>>();
+  dropped_modules.sort_by(|a, b| a.uri.cmp(&b.uri));
+  compilation.stats.dropped_modules = dropped_modules;
 
   if true {
     let empty_chunk_id_to_be_removed = chunk_graph
@@ -156,6 +176,16 @@ pub fn split_code(compilation: &mut Compilation) {
   }
 }
 
+fn dropped_reason(module: &ModuleGraphModule) -> String {
+  if module.has_side_effects() {
+    String::from("only imported by dropped modules")
+  } else if module.side_effects == Some(false) {
+    String::from("marked side effect free by `sideEffects` in package.json and none of its exports is used")
+  } else {
+    String::from("has no side effects and none of its exports is used")
+  }
+}
+
 struct ChunkIdGenerator<'a> {
   id: usize,
   chunk_id_algo: ChunkIdAlgo,
@@ -1,23 +1,28 @@
 use std::sync::{Arc};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, VecDeque};
 use dashmap::DashSet;
 use hashbrown::HashSet;
+use indexmap::IndexMap;
 
-use crate::{ComplierOptions, EntryItem, ModuleGraph, Dependency, ResolveKind, ChunkGraph, split_chunker::split_code};
+use crate::{ComplierOptions, EntryItem, ModuleGraph, Dependency, ResolveKind, ChunkGraph, split_chunker::split_code, Asset, AssetFilename, PluginDriver, RenderManifestArgs, Diagnostic, render_css_chunks, Stats, concatenate_modules, ModuleExplanation, DotOptions};
 
 #[derive(Debug, Default)]
 pub struct Compilation {
   pub options: Arc<ComplierOptions>,
-  pub entries: HashMap<String, EntryItem>,
+  pub entries: IndexMap<String, EntryItem>,
   pub (crate) visited_module_id: Arc<DashSet<String>>,
   pub module_graph: ModuleGraph,
   pub chunk_graph: ChunkGraph,
+  /// Rendered assets keyed by output filename.
+  pub assets: BTreeMap<String, Asset>,
+  pub diagnostics: Vec<Diagnostic>,
+  pub stats: Stats,
 }
 
 impl Compilation {
     pub fn new(
         options: Arc<ComplierOptions>,
-        entries: HashMap<String, EntryItem>,
+        entries: IndexMap<String, EntryItem>,
         visited_module_id: Arc<DashSet<String>>,
         module_graph: ModuleGraph,
     ) -> Self {
@@ -27,9 +32,16 @@ impl Compilation {
             visited_module_id,
             module_graph,
             chunk_graph: Default::default(),
+            assets: Default::default(),
+            diagnostics: Default::default(),
+            stats: Default::default(),
         }
     }
 
+    pub fn push_diagnostic(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
     pub fn add_entry(&mut self, key: String, value: EntryItem) {
         self.entries.insert(key, value);
     }
@@ -47,40 +59,188 @@ impl Compilation {
             .collect()
     }
 
+    /// Uris of the entry modules, in declaration order.
+    pub fn entry_uris(&self) -> Vec<String> {
+        self.entries_dependencies()
+            .iter()
+            .filter_map(|dep| self.module_graph.uri_by_dependency(dep))
+            .map(|uri| uri.to_string())
+            .collect()
+    }
+
+    pub fn calc_used_exports(&mut self) {
+        let entries = self.entry_uris();
+        self.module_graph.calc_used_exports(&entries);
+    }
+
+    /// See `ModuleGraph::explain`.
+    pub fn explain(&self, uri: &str) -> Option<ModuleExplanation> {
+        self.module_graph
+            .explain(uri, &self.entry_uris(), &self.chunk_graph)
+    }
+
+    /// Numbers modules in ESM evaluation order: a module comes after all of its
+    /// static dependencies, in the order they are imported. In a cycle, the module
+    /// reached first is evaluated last. Dynamically imported modules are numbered
+    /// after the static graph of the entries, in the order they are reached. Entries
+    /// are visited in declaration order. Dependencies on modules which failed to build
+    /// are skipped.
     pub fn calc_exec_order(&mut self) {
-        let mut stack = self
+        let mut roots = self
             .entries_dependencies()
             .iter()
-            .filter_map(|dep| self.module_graph.module_by_dependency(dep))
-            .map(|module| module.uri.clone())
-            .collect::<Vec<_>>();
+            .filter_map(|dep| self.module_graph.uri_by_dependency(dep))
+            .map(|uri| uri.to_string())
+            .collect::<VecDeque<_>>();
+
         let mut visited: HashSet<String> = HashSet::new();
-        let mut next_exce_order = 0;
-        while let Some(uri) = stack.pop() {
-            let module = self.module_graph.module_by_uri(&uri).unwrap();
-            if !visited.contains(&uri) {
-                visited.insert(uri.clone());
-                module
-                    .depended_modules(&self.module_graph)
-                    .into_iter()
-                    .rev()
-                    .for_each(|dep| {
-                        stack.push(dep.uri.clone());
-                    });
-                self
-                    .module_graph
-                    .module_by_uri_mut(&uri)
-                    .unwrap()
-                    .exec_order = next_exce_order;
-                next_exce_order += 1;
+        let mut order = vec![];
+        while let Some(root) = roots.pop_front() {
+            // (uri, whether its dependencies are pushed)
+            let mut stack = vec![(root, false)];
+            while let Some((uri, expanded)) = stack.pop() {
+                if expanded {
+                    order.push(uri);
+                    continue;
+                }
+                if !visited.insert(uri.clone()) {
+                    continue;
+                }
+                let module = match self.module_graph.module_by_uri(&uri) {
+                    Some(module) => module,
+                    None => continue,
+                };
+                stack.push((uri.clone(), true));
+                let mut static_deps = vec![];
+                for dep in &module.dependencies {
+                    let dep_uri = match self.module_graph.uri_by_dependency(dep) {
+                        Some(dep_uri) => dep_uri,
+                        None => continue,
+                    };
+                    if matches!(dep.kind, ResolveKind::DynamicImport) {
+                        roots.push_back(dep_uri.to_string());
+                    } else {
+                        static_deps.push(dep_uri);
+                    }
+                }
+                stack.extend(
+                    static_deps
+                        .into_iter()
+                        .rev()
+                        .filter(|dep_uri| !visited.contains(*dep_uri))
+                        .map(|dep_uri| (dep_uri.to_string(), false)),
+                );
+            }
+        }
+
+        for (exec_order, uri) in order.iter().enumerate() {
+            if let Some(module) = self.module_graph.module_by_uri_mut(uri) {
+                module.exec_order = exec_order;
             }
         }
+    }
 
-        let mut modules = self.module_graph.modules().collect::<Vec<_>>();
-        modules.sort_by_key(|module| module.exec_order);
+    /// See `ModuleGraph::to_dot`.
+    pub fn module_graph_dot(&self, options: &DotOptions) -> String {
+        self.module_graph.to_dot(&self.entry_uris(), options)
     }
 
-    pub fn seal(&mut self) {
+    /// See `ChunkGraph::to_dot`.
+    pub fn chunk_graph_dot(&self, options: &DotOptions) -> String {
+        self.chunk_graph
+            .to_dot(&self.module_graph, &self.entry_uris(), options)
+    }
+
+    pub async fn seal(&mut self, plugin_driver: &PluginDriver) -> anyhow::Result<()> {
         split_code(self);
+        plugin_driver.optimize_chunks(self).await?;
+        if self.options.optimization.concatenate_modules {
+            concatenate_modules(self);
+        }
+        self.emit_module_assets();
+        render_css_chunks(self);
+        self.render_manifest(plugin_driver)?;
+        plugin_driver.process_assets(self).await
+    }
+
+    /// Collects the files emitted by modules which are placed in a chunk.
+    pub fn emit_module_assets(&mut self) {
+        let assets = self
+            .chunk_graph
+            .chunks()
+            .flat_map(|chunk| chunk.module_uris.iter())
+            .filter_map(|uri| self.module_graph.module_by_uri(uri))
+            .flat_map(|module| module.module.assets())
+            .map(|asset| (asset_filename(&asset.filename, ""), asset))
+            .collect::<Vec<_>>();
+        self.assets.extend(assets);
+    }
+
+    pub fn render_manifest(&mut self, plugin_driver: &PluginDriver) -> anyhow::Result<()> {
+        let mut assets = vec![];
+        for chunk in self.chunk_graph.chunks() {
+            let rendered = plugin_driver.render_manifest(RenderManifestArgs {
+                chunk_id: chunk.id.as_str(),
+                compilation: self,
+            })?;
+            assets.extend(
+                rendered
+                    .into_iter()
+                    .map(|asset| (asset_filename(&asset.filename, chunk.id.as_str()), asset)),
+            );
+        }
+        self.assets.extend(assets);
+        Ok(())
+    }
+}
+
+fn asset_filename(filename: &AssetFilename, chunk_id: &str) -> String {
+    match filename {
+        AssetFilename::Static(name) => name.clone(),
+        AssetFilename::Templace(template) => template.replace("[name]", chunk_id),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Module, ModuleGraphModule, SourceType};
+
+    #[derive(Debug)]
+    struct TestModule;
+
+    impl Module for TestModule {
+        fn render(&self) -> String {
+            String::new()
+        }
+    }
+
+    fn dependency(importer: Option<&str>, specifier: &str) -> Dependency {
+        Dependency {
+            importer: importer.map(|importer| importer.to_string()),
+            specifier: specifier.to_string(),
+            kind: ResolveKind::Import,
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn calc_exec_order_skips_modules_which_failed_to_build() {
+        let mut compilation = Compilation::default();
+        compilation.add_entry(String::from("main"), EntryItem::from(String::from("a.js")));
+        compilation.add_entry(String::from("broken"), EntryItem::from(String::from("broken.js")));
+
+        let a_deps = vec![dependency(Some("/a.js"), "./b.js"), dependency(Some("/a.js"), "./c.js")];
+        let graph = &mut compilation.module_graph;
+        graph.add_dependency(dependency(None, "a.js"), String::from("/a.js"));
+        graph.add_dependency(dependency(None, "broken.js"), String::from("/broken.js"));
+        graph.add_dependency(a_deps[0].clone(), String::from("/b.js"));
+        graph.add_dependency(a_deps[1].clone(), String::from("/c.js"));
+        graph.add_module(ModuleGraphModule::new(Box::new(TestModule), String::from("/a.js"), SourceType::Js, a_deps));
+        graph.add_module(ModuleGraphModule::new(Box::new(TestModule), String::from("/c.js"), SourceType::Js, vec![]));
+
+        compilation.calc_exec_order();
+
+        let exec_order = |uri| compilation.module_graph.module_by_uri(uri).unwrap().exec_order;
+        assert_eq!(exec_order("/c.js"), 0);
+        assert_eq!(exec_order("/a.js"), 1);
+    }
+}
@@ -1,18 +1,19 @@
 mod compilation;
 pub use compilation::*;
-use nodejs_resolver::Resolver;
 
-use crate::{Dependency, ModuleGraphModule, ComplierOptions, PluginDriver, Plugin, JobContext, ResolveModuleJob};
+use crate::{Dependency, ModuleGraphModule, ComplierOptions, PluginDriver, Plugin, JobContext, ResolveModuleJob, ResolveKind, create_resolver, Diagnostic};
 use std::sync::{
   atomic::{AtomicUsize, Ordering},
   Arc
 };
+use std::path::Path;
 
 #[derive(Debug)]
 pub enum Msg {
   DependencyReference(Dependency, String),
   TaskFinished(Box<ModuleGraphModule>),
   TaskErrorEncountered(()),
+  TaskFailed(Diagnostic),
 }
 
 pub struct Complier {
@@ -24,18 +25,14 @@ pub struct Complier {
 impl Complier {
   pub fn new(options: ComplierOptions, plugins: Vec<Box<dyn Plugin>>) -> Self {
     let options = Arc::new(options);
+    let resolvers = ResolveKind::all()
+      .into_iter()
+      .map(|kind| (kind, Arc::new(create_resolver(&options, kind))))
+      .collect();
     let plugin_driver = PluginDriver::new(
       options.clone(),
       plugins,
-      Arc::new(Resolver::new(nodejs_resolver::ResolverOptions {
-        // prefer_relative: false,
-        extensions: vec![".tsx", ".jsx", ".ts", ".js", ".json"]
-          .into_iter()
-          .map(|s| s.to_string())
-          .collect(),
-        alias_fields: vec![String::from("browser")],
-        ..Default::default()
-      }))
+      resolvers,
     );
     Self {
       options,
@@ -44,14 +41,23 @@ impl Complier {
     }
   }
 
-  pub async fn compile(&mut self) {
+  pub async fn compile(&mut self) -> anyhow::Result<()> {
+    self.plugin_driver.before_compile().await?;
+
     self.compilation = Compilation::new(
       self.options.clone(),
       self.options.entries.clone(),
       Default::default(),
       Default::default(),
     );
+    self
+      .compilation
+      .diagnostics
+      .extend(self.plugin_driver.diagnostics.iter().cloned());
+    self.plugin_driver.this_compilation(&mut self.compilation).await?;
+    self.plugin_driver.make(&mut self.compilation).await?;
 
+    let diagnostics_before_build = self.compilation.diagnostics.len();
     let active_task_count = Arc::new(AtomicUsize::new(0));
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Msg>();
 
@@ -87,15 +93,96 @@ impl Complier {
             Msg::TaskErrorEncountered(()) => {
               active_task_count.fetch_sub(1, Ordering::SeqCst);
             }
+            Msg::TaskFailed(diagnostic) => {
+              active_task_count.fetch_sub(1, Ordering::SeqCst);
+              self.compilation.push_diagnostic(diagnostic);
+            }
           }
           None => {
             tracing::trace!("no more job")
           }
         }
     }
-    
+
+    // Modules are built concurrently, sort what they reported so the order does not
+    // depend on which task finished first.
+    self.compilation.diagnostics[diagnostics_before_build..].sort_by(|a, b| {
+      (&a.uri, &a.title, &a.message).cmp(&(&b.uri, &b.title, &b.message))
+    });
+    let module_diagnostics = self
+      .compilation
+      .module_graph
+      .modules()
+      .flat_map(|module| module.module.diagnostics())
+      .collect::<Vec<_>>();
+    self.compilation.diagnostics.extend(module_diagnostics);
+
+    self.plugin_driver.finish_modules(&mut self.compilation).await?;
+
     self.compilation.calc_exec_order();
+    self.compilation.calc_used_exports();
+
+    self.compilation.seal(&self.plugin_driver).await?;
+
+    self.emit_assets().await?;
+    self.plugin_driver.after_emit(&self.compilation).await?;
+
+    self.plugin_driver.done(&self.compilation).await
+  }
+
+  pub async fn emit_assets(&self) -> anyhow::Result<()> {
+    let output_path = Path::new(self.options.root.as_str()).join(&self.options.output.path);
+    for (filename, asset) in &self.compilation.assets {
+      let file_path = output_path.join(filename);
+      if let Some(dir) = file_path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+      }
+      tokio::fs::write(&file_path, asset.rendered.as_bytes()).await?;
+    }
+    Ok(())
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_utils::{write_fixture, TestParserPlugin};
+  use crate::EntryItem;
 
-    self.compilation.seal();
+  #[tokio::test]
+  async fn compiles_cycles_and_shared_dependencies() {
+    let root = write_fixture(
+      "cycle",
+      &[
+        ("a.js", "import ./b.js\nimport ./d.js"),
+        ("b.js", "import ./c.js\nimport ./d.js"),
+        ("c.js", "import ./a.js"),
+        ("d.js", ""),
+      ],
+    );
+    let mut options = ComplierOptions {
+      root: root.to_string_lossy().to_string(),
+      ..Default::default()
+    };
+    options
+      .entries
+      .insert(String::from("main"), EntryItem::from(String::from("./a.js")));
+    let mut complier = Complier::new(options, vec![Box::new(TestParserPlugin)]);
+
+    complier.compile().await.unwrap();
+
+    let compilation = &complier.compilation;
+    assert!(compilation.diagnostics.is_empty(), "{:?}", compilation.diagnostics);
+    let exec_order = |name: &str| {
+      let uri = root.join(name).to_string_lossy().to_string();
+      compilation.module_graph.module_by_uri(&uri).unwrap().exec_order
+    };
+    assert_eq!(
+      ["c.js", "d.js", "b.js", "a.js"].map(exec_order),
+      [0, 1, 2, 3]
+    );
+    let chunks = compilation.chunk_graph.chunks().collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].module_uris.len(), 4);
+    std::fs::remove_dir_all(root).unwrap();
   }
-}
\ No newline at end of file
+}
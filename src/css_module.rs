@@ -0,0 +1,541 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use sugar_path::PathSugar;
+
+use crate::{
+  hash_content, Asset, AssetFilename, Compilation, ComplierOptions, Diagnostic, Module,
+  ModuleDependency, ParseModuleArgs, ResolveKind, SourceType,
+};
+
+static AT_IMPORT_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(r#"@import\s+(?:url\(\s*)?["']?([^"')\s;]+)["']?\s*\)?([^;]*);"#).unwrap()
+});
+static URL_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r#"url\(\s*["']?([^"')]+?)["']?\s*\)"#).unwrap());
+static CLASS_RE: Lazy<Regex> = Lazy::new(|| {
+  let escape = r"\\[0-9a-fA-F]{1,6} ?|\\[^0-9a-fA-F\n]";
+  Regex::new(&format!(
+    r"\.(-?(?:[_a-zA-Z]|[^\x00-\x7F]|{escape})(?:[\w-]|[^\x00-\x7F]|{escape})*)",
+    escape = escape
+  ))
+  .unwrap()
+});
+static HASH_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[hash(?::(\d+))?\]").unwrap());
+static GLOBAL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r":global\(([^)]*)\)").unwrap());
+
+/// Built-in module for `.css` files, used when no plugin registers a parser for
+/// `SourceType::Css`.
+///
+/// `@import` rules are removed from the rendered css, the imported modules are
+/// placed before the importer in the css asset of the chunk instead. Imports of urls
+/// which are not bundled, e.g. `https://...`, are kept as is. Comments are removed.
+#[derive(Debug)]
+pub struct CssModule {
+  pub uri: String,
+  pub source: String,
+  pub imports: Vec<String>,
+  pub urls: Vec<String>,
+  /// Original class names to generated ones, only set for css modules.
+  pub exports: Option<BTreeMap<String, String>>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl CssModule {
+  pub fn parse(args: ParseModuleArgs, options: &ComplierOptions) -> anyhow::Result<Self> {
+    let source = strip_comments(&args.source.try_into_string()?);
+    let mut imports = vec![];
+    let mut diagnostics = vec![];
+    let source = AT_IMPORT_RE
+      .replace_all(&source, |caps: &Captures| {
+        let url = &caps[1];
+        if !is_bundled_url(url) {
+          return caps[0].to_string();
+        }
+        let condition = caps[2].trim();
+        if !condition.is_empty() {
+          diagnostics.push(
+            Diagnostic::warn(
+              "Unsupported @import condition",
+              format!(
+                "{} is bundled unconditionally, the condition `{}` is ignored",
+                url, condition
+              ),
+            )
+            .with_uri(args.uri.to_string()),
+          );
+        }
+        imports.push(url.to_string());
+        String::new()
+      })
+      .to_string();
+    let css_options = &options.module.css;
+    let path = args.resource.path;
+    let (source, exports) = if css_options.modules.matches(path) {
+      let relative_uri = Path::new(path).relative(&options.root);
+      let relative_uri = relative_uri.to_string_lossy();
+      let (source, exports) = scope_class_names(&source, |local| {
+        local_ident_name(&css_options.local_ident_name, path, &relative_uri, local)
+      });
+      (source, Some(exports))
+    } else {
+      (source, None)
+    };
+    let mut seen = HashSet::new();
+    let urls = URL_RE
+      .captures_iter(&source)
+      .map(|caps| caps[1].to_string())
+      .filter(|url| is_bundled_url(url) && seen.insert(url.clone()))
+      .collect();
+    Ok(Self {
+      uri: args.uri.to_string(),
+      source,
+      imports,
+      urls,
+      exports,
+      diagnostics,
+    })
+  }
+}
+
+impl Module for CssModule {
+  fn render(&self) -> String {
+    self.source.clone()
+  }
+
+  fn dependencies(&mut self) -> Vec<ModuleDependency> {
+    self
+      .imports
+      .iter()
+      .map(|specifier| ModuleDependency {
+        specifier: specifier.clone(),
+        kind: ResolveKind::AtImport,
+      })
+      .chain(self.urls.iter().map(|specifier| ModuleDependency {
+        specifier: specifier.clone(),
+        kind: ResolveKind::Url,
+      }))
+      .collect()
+  }
+
+  fn diagnostics(&self) -> Vec<Diagnostic> {
+    self.diagnostics.clone()
+  }
+
+  fn render_js_exports(&self) -> Option<String> {
+    let exports = self.exports.as_ref()?;
+    Some(format!(
+      "module.exports = {};",
+      serde_json::to_string(exports).unwrap()
+    ))
+  }
+}
+
+/// Rewrites the class names in selectors with `rename`, except those wrapped in
+/// `:global(...)`. Returns the rewritten css and the mapping of renamed classes.
+///
+/// Comments and strings are kept as is, and class names are unescaped before being
+/// renamed, e.g. `.icon-\31 23` exports `icon-123`.
+fn scope_class_names(
+  css: &str,
+  rename: impl Fn(&str) -> String,
+) -> (String, BTreeMap<String, String>) {
+  let mut exports = BTreeMap::new();
+  let mut output = String::with_capacity(css.len());
+  let mut rest = css;
+  while let Some(index) = find_unquoted(rest, &['{', '}']) {
+    let (segment, tail) = rest.split_at(index);
+    let is_prelude = tail.starts_with('{') && !skip_comments(segment).starts_with('@');
+    if is_prelude {
+      let mut last = 0;
+      for global in GLOBAL_RE.captures_iter(segment) {
+        let whole = global.get(0).unwrap();
+        output.push_str(&scope_selector(
+          &segment[last..whole.start()],
+          &rename,
+          &mut exports,
+        ));
+        output.push_str(&global[1]);
+        last = whole.end();
+      }
+      output.push_str(&scope_selector(&segment[last..], &rename, &mut exports));
+    } else {
+      output.push_str(segment);
+    }
+    output.push_str(&tail[..1]);
+    rest = &tail[1..];
+  }
+  output.push_str(rest);
+  (output, exports)
+}
+
+/// Attribute selectors, strings and comments of `selector` are kept as is.
+fn scope_selector(
+  selector: &str,
+  rename: &impl Fn(&str) -> String,
+  exports: &mut BTreeMap<String, String>,
+) -> String {
+  let mut output = String::with_capacity(selector.len());
+  let mut rest = selector;
+  while let Some(index) = rest
+    .char_indices()
+    .find(|(index, c)| matches!(c, '[' | '"' | '\'') || rest[*index..].starts_with("/*"))
+    .map(|(index, _)| index)
+  {
+    let (scoped, tail) = rest.split_at(index);
+    output.push_str(&scope_class_selectors(scoped, rename, exports));
+    let end = verbatim_len(tail);
+    output.push_str(&tail[..end]);
+    rest = &tail[end..];
+  }
+  output.push_str(&scope_class_selectors(rest, rename, exports));
+  output
+}
+
+/// Index of the first of `chars` in `css` outside of strings and comments.
+fn find_unquoted(css: &str, chars: &[char]) -> Option<usize> {
+  let mut index = 0;
+  while let Some(c) = css[index..].chars().next() {
+    if c == '"' || c == '\'' || css[index..].starts_with("/*") {
+      index += verbatim_len(&css[index..]);
+    } else if chars.contains(&c) {
+      return Some(index);
+    } else {
+      index += c.len_utf8();
+    }
+  }
+  None
+}
+
+/// `css` without its comments, strings are kept as is.
+fn strip_comments(css: &str) -> String {
+  let mut output = String::with_capacity(css.len());
+  let mut index = 0;
+  while let Some(c) = css[index..].chars().next() {
+    if c == '"' || c == '\'' || css[index..].starts_with("/*") {
+      let len = verbatim_len(&css[index..]);
+      if c != '/' {
+        output.push_str(&css[index..index + len]);
+      }
+      index += len;
+    } else {
+      output.push(c);
+      index += c.len_utf8();
+    }
+  }
+  output
+}
+
+/// `css` without its leading whitespace and comments.
+fn skip_comments(css: &str) -> &str {
+  let mut css = css.trim_start();
+  while css.starts_with("/*") {
+    css = css[verbatim_len(css)..].trim_start();
+  }
+  css
+}
+
+/// Length of the attribute selector, string or comment at the start of `css`.
+fn verbatim_len(css: &str) -> usize {
+  if let Some(comment) = css.strip_prefix("/*") {
+    return comment.find("*/").map_or(css.len(), |end| end + 4);
+  }
+  let close = match css.as_bytes()[0] {
+    b'[' => b']',
+    quote => quote,
+  };
+  let mut quote = None;
+  let mut escaped = false;
+  for (index, byte) in css.bytes().enumerate().skip(1) {
+    if escaped {
+      escaped = false;
+      continue;
+    }
+    match quote {
+      _ if byte == b'\\' => escaped = true,
+      Some(current) if byte == current => quote = None,
+      Some(_) => {}
+      None if byte == close => return index + 1,
+      None if close == b']' && (byte == b'"' || byte == b'\'') => quote = Some(byte),
+      None => {}
+    }
+  }
+  css.len()
+}
+
+fn scope_class_selectors(
+  selector: &str,
+  rename: &impl Fn(&str) -> String,
+  exports: &mut BTreeMap<String, String>,
+) -> String {
+  CLASS_RE
+    .replace_all(selector, |caps: &Captures| {
+      let local = unescape_ident(&caps[1]);
+      let scoped = exports
+        .entry(local.clone())
+        .or_insert_with(|| rename(&local));
+      format!(".{}", escape_ident(scoped))
+    })
+    .to_string()
+}
+
+fn unescape_ident(ident: &str) -> String {
+  let mut output = String::with_capacity(ident.len());
+  let mut chars = ident.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      output.push(c);
+      continue;
+    }
+    let mut hex = String::new();
+    while let Some(digit) = chars
+      .peek()
+      .filter(|digit| digit.is_ascii_hexdigit() && hex.len() < 6)
+    {
+      hex.push(*digit);
+      chars.next();
+    }
+    if hex.is_empty() {
+      output.extend(chars.next());
+    } else {
+      let code_point = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+      output.push(code_point.unwrap_or(char::REPLACEMENT_CHARACTER));
+      chars.next_if_eq(&' ');
+    }
+  }
+  output
+}
+
+fn escape_ident(ident: &str) -> String {
+  ident
+    .chars()
+    .enumerate()
+    .map(|(index, c)| match c {
+      '0'..='9' if index == 0 => format!("\\3{} ", c),
+      '0'..='9' | 'a'..='z' | 'A'..='Z' | '_' | '-' => c.to_string(),
+      c if !c.is_ascii() => c.to_string(),
+      c => format!("\\{}", c),
+    })
+    .collect()
+}
+
+fn local_ident_name(template: &str, path: &str, relative_uri: &str, local: &str) -> String {
+  let name = Path::new(path)
+    .file_stem()
+    .and_then(|name| name.to_str())
+    .unwrap_or_default();
+  let name = name.strip_suffix(".module").unwrap_or(name);
+  let hash = hash_content(format!("{}{}", relative_uri, local).as_bytes());
+  let ident = HASH_RE.replace_all(template, |caps: &Captures| {
+    let len = caps
+      .get(1)
+      .and_then(|len| len.as_str().parse::<usize>().ok())
+      .unwrap_or(hash.len())
+      .min(hash.len());
+    hash[..len].to_string()
+  });
+  ident.replace("[name]", name).replace("[local]", local)
+}
+
+/// Replaces the `url()` references of `css` found in `urls`.
+pub fn replace_urls(css: &str, urls: &HashMap<&str, String>) -> String {
+  URL_RE
+    .replace_all(css, |caps: &Captures| match urls.get(&caps[1]) {
+      Some(url) => format!("url({})", serde_json::Value::String(url.clone())),
+      None => caps[0].to_string(),
+    })
+    .to_string()
+}
+
+fn is_bundled_url(url: &str) -> bool {
+  !(url.starts_with("data:")
+    || url.starts_with('#')
+    || url.starts_with("//")
+    || url.contains("://"))
+}
+
+/// Renders the css modules of every chunk to `[chunk_id].css`.
+///
+/// Modules come after the modules they `@import`. A warning is reported when two
+/// chunks place the same pair of css modules in different order, since the rules of
+/// one of them will be applied in an unexpected order once both are loaded.
+pub fn render_css_chunks(compilation: &mut Compilation) {
+  let module_graph = &compilation.module_graph;
+  let mut assets = vec![];
+  let mut orders: Vec<(&str, Vec<&str>)> = vec![];
+  for chunk in compilation.chunk_graph.chunks() {
+    let css_modules = chunk
+      .import_ordered_module(module_graph)
+      .into_iter()
+      .filter(|module| module.source_type == SourceType::Css)
+      .collect::<Vec<_>>();
+    if css_modules.is_empty() {
+      continue;
+    }
+    let rendered = css_modules
+      .iter()
+      .map(|module| {
+        let urls = module
+          .dependencies
+          .iter()
+          .filter(|dep| matches!(dep.kind, ResolveKind::Url))
+          .filter_map(|dep| {
+            let url = module_graph.module_by_dependency(dep)?.module.url()?;
+            Some((dep.specifier.as_str(), url))
+          })
+          .collect::<HashMap<_, _>>();
+        replace_urls(&module.render(), &urls)
+      })
+      .collect::<Vec<_>>()
+      .join("\n");
+    assets.push((
+      format!("{}.css", chunk.id),
+      Asset {
+        rendered: rendered.into(),
+        filename: AssetFilename::Static(format!("{}.css", chunk.id)),
+      },
+    ));
+    orders.push((
+      chunk.id.as_str(),
+      css_modules
+        .iter()
+        .map(|module| module.uri.as_str())
+        .collect(),
+    ));
+  }
+
+  let mut diagnostics = vec![];
+  for (index, (chunk_a, order_a)) in orders.iter().enumerate() {
+    for (chunk_b, order_b) in &orders[index + 1..] {
+      let position_b = order_b
+        .iter()
+        .enumerate()
+        .map(|(position, uri)| (*uri, position))
+        .collect::<HashMap<_, _>>();
+      let common = order_a
+        .iter()
+        .filter_map(|uri| Some((*uri, *position_b.get(uri)?)))
+        .collect::<Vec<_>>();
+      // `common` follows the order of chunk a, so any decreasing position in chunk b is a conflict.
+      if let Some(pair) = common.windows(2).find(|pair| pair[0].1 > pair[1].1) {
+        diagnostics.push(Diagnostic::warn(
+          "Conflicting css order",
+          format!(
+            "{} is placed before {} in chunk {} but after it in chunk {}",
+            pair[0].0, pair[1].0, chunk_a, chunk_b
+          ),
+        ));
+      }
+    }
+  }
+
+  compilation.assets.extend(assets);
+  compilation.diagnostics.extend(diagnostics);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Content, ResourceData};
+
+  fn parse(css: &str) -> CssModule {
+    let args = ParseModuleArgs {
+      uri: "/a.css",
+      resource: ResourceData::parse("/a.css"),
+      source: Content::String(css.to_string()),
+      map: None,
+    };
+    CssModule::parse(args, &ComplierOptions::default()).unwrap()
+  }
+
+  fn scope(css: &str) -> (String, Vec<String>) {
+    let (css, exports) = scope_class_names(css, |local| format!("x_{}", local));
+    (css, exports.into_keys().collect())
+  }
+
+  #[test]
+  fn scopes_class_names_nested_in_at_rules() {
+    assert_eq!(
+      scope("@media (min-width: 10.5em) { .a, .b:hover { color: red } }"),
+      (
+        String::from("@media (min-width: 10.5em) { .x_a, .x_b:hover { color: red } }"),
+        vec![String::from("a"), String::from("b")]
+      )
+    );
+  }
+
+  #[test]
+  fn keeps_global_class_names() {
+    assert_eq!(
+      scope(":global(.g) .l { }"),
+      (String::from(".g .x_l { }"), vec![String::from("l")])
+    );
+  }
+
+  #[test]
+  fn skips_attribute_selectors_and_strings() {
+    assert_eq!(
+      scope(r#"a[href$=".pdf"].link { content: "}.no" } .a[title='.x'] { }"#),
+      (
+        String::from(r#"a[href$=".pdf"].x_link { content: "}.no" } .x_a[title='.x'] { }"#),
+        vec![String::from("a"), String::from("link")]
+      )
+    );
+  }
+
+  #[test]
+  fn skips_comments() {
+    assert_eq!(
+      scope("/* .comment { } */ .real { } /* x */ @media print { .p { } }"),
+      (
+        String::from("/* .comment { } */ .x_real { } /* x */ @media print { .x_p { } }"),
+        vec![String::from("p"), String::from("real")]
+      )
+    );
+  }
+
+  #[test]
+  fn leaves_keyframes_alone() {
+    assert_eq!(
+      scope("@keyframes fade { from { opacity: 0.5 } 50.5% { } to { } } .a { animation: fade 1s }"),
+      (
+        String::from(
+          "@keyframes fade { from { opacity: 0.5 } 50.5% { } to { } } .x_a { animation: fade 1s }"
+        ),
+        vec![String::from("a")]
+      )
+    );
+  }
+
+  #[test]
+  fn unescapes_class_names() {
+    assert_eq!(
+      scope(r".icon-\31 23 { } .a\.b { }"),
+      (
+        String::from(r".x_icon-123 { } .x_a\.b { }"),
+        vec![String::from("a.b"), String::from("icon-123")]
+      )
+    );
+  }
+
+  #[test]
+  fn ignores_imports_and_urls_in_comments() {
+    let module = parse(
+      "/* @import './commented.css'; url(./commented.png) */\n@import './b.css';\n.a { content: \"/* kept */\"; background: url(./a.png) }",
+    );
+    assert_eq!(module.imports, ["./b.css"]);
+    assert_eq!(module.urls, ["./a.png"]);
+    assert_eq!(
+      module.source,
+      "\n\n.a { content: \"/* kept */\"; background: url(./a.png) }"
+    );
+  }
+
+  #[test]
+  fn dedupes_urls_in_first_occurrence_order() {
+    let module = parse(".a { background: url(./a.png) } .b { background: url(./b.png) } .c { background: url(./a.png) }");
+    assert_eq!(module.urls, ["./a.png", "./b.png"]);
+  }
+}
@@ -0,0 +1,60 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  pub title: String,
+  pub message: String,
+  /// Uri of the module the diagnostic is about. For a request which can not be
+  /// resolved, the uri of the importer.
+  pub uri: Option<String>,
+}
+
+impl Diagnostic {
+  pub fn error(title: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      severity: Severity::Error,
+      title: title.into(),
+      message: message.into(),
+      uri: None,
+    }
+  }
+
+  pub fn warn(title: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      severity: Severity::Warning,
+      title: title.into(),
+      message: message.into(),
+      uri: None,
+    }
+  }
+
+  pub fn with_uri(mut self, uri: impl Into<String>) -> Self {
+    self.uri = Some(uri.into());
+    self
+  }
+
+  pub fn is_error(&self) -> bool {
+    matches!(self.severity, Severity::Error)
+  }
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let severity = match self.severity {
+      Severity::Error => "error",
+      Severity::Warning => "warning",
+    };
+    write!(f, "{}[{}]: {}", severity, self.title, self.message)?;
+    if let Some(uri) = &self.uri {
+      write!(f, "\n  --> {}", uri)?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,19 @@
+use crate::Module;
+
+/// Module standing for an ignored request, e.g. `fs` mapped to `false` in the
+/// `browser` field of `package.json`.
+#[derive(Debug)]
+pub struct EmptyModule;
+
+impl EmptyModule {
+  /// Uri of the module in the module graph.
+  pub fn uri(specifier: &str) -> String {
+    format!("ignored {}", specifier)
+  }
+}
+
+impl Module for EmptyModule {
+  fn render(&self) -> String {
+    String::from("module.exports = {};")
+  }
+}
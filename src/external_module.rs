@@ -0,0 +1,72 @@
+use crate::{hash_content, External, HoistedImport, Module};
+
+/// Module standing for a request matched by `externals`, rendered as a reference
+/// to the external instead of the bundled code.
+#[derive(Debug)]
+pub struct ExternalModule {
+  pub specifier: String,
+  pub external: External,
+}
+
+impl ExternalModule {
+  pub fn new(specifier: String, external: External) -> Self {
+    Self {
+      specifier,
+      external,
+    }
+  }
+
+  /// Uri of the module in the module graph.
+  pub fn uri(external: &External) -> String {
+    match external {
+      External::Global(name) => format!("external global {}", name),
+      External::CommonJs(request) => format!("external commonjs {}", request),
+      External::Module(request) => format!("external module {}", request),
+      External::Script { url, global } => format!("external script {}@{}", global, url),
+    }
+  }
+}
+
+impl Module for ExternalModule {
+  fn render(&self) -> String {
+    match &self.external {
+      External::Global(name) => format!("module.exports = {};", name),
+      External::CommonJs(request) => format!(
+        "module.exports = require({});",
+        serde_json::Value::String(request.clone())
+      ),
+      External::Module(request) => format!("module.exports = {};", hoisted_local(request)),
+      External::Script { url, global } => format!(
+        r#"module.exports = new Promise(function (resolve, reject) {{
+  if (typeof {global} !== "undefined") return resolve({global});
+  var script = document.createElement("script");
+  script.src = {url};
+  script.onload = function () {{ resolve({global}); }};
+  script.onerror = reject;
+  document.head.appendChild(script);
+}});"#,
+        global = global,
+        url = serde_json::Value::String(url.clone())
+      ),
+    }
+  }
+
+  /// A static `import` is not allowed in the module wrapper, so it is hoisted.
+  fn hoisted_import(&self) -> Option<HoistedImport> {
+    match &self.external {
+      External::Module(request) => Some(HoistedImport {
+        request: request.clone(),
+        local: hoisted_local(request),
+      }),
+      _ => None,
+    }
+  }
+
+  fn is_async(&self) -> bool {
+    matches!(self.external, External::Script { .. })
+  }
+}
+
+fn hoisted_local(request: &str) -> String {
+  format!("__external_module_{}__", &hash_content(request.as_bytes())[..8])
+}
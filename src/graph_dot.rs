@@ -0,0 +1,112 @@
+use std::collections::{HashMap, VecDeque};
+
+use petgraph::dot::Dot;
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use crate::{ChunkGraph, ModuleGraph};
+
+/// Limits which modules are rendered by `ModuleGraph::to_dot` and `ChunkGraph::to_dot`.
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+  /// Only modules whose uri starts with the prefix.
+  pub path_prefix: Option<String>,
+  /// Only modules at most this many dependencies away from an entry.
+  pub depth: Option<usize>,
+}
+
+impl DotOptions {
+  /// Uris of the modules to render, in module graph order.
+  fn filter_modules<'a>(&self, module_graph: &'a ModuleGraph, entries: &[String]) -> Vec<&'a str> {
+    let depths = self.depth.map(|max_depth| {
+      let mut depths: HashMap<&str, usize> = HashMap::new();
+      let mut queue = VecDeque::new();
+      for entry in entries {
+        if let Some(module) = module_graph.module_by_uri(entry) {
+          depths.insert(module.uri.as_str(), 0);
+          queue.push_back((module.uri.as_str(), 0));
+        }
+      }
+      while let Some((uri, depth)) = queue.pop_front() {
+        if depth == max_depth {
+          continue;
+        }
+        for connection in module_graph.outgoing_connections(uri) {
+          if !depths.contains_key(connection.module_uri) {
+            depths.insert(connection.module_uri, depth + 1);
+            queue.push_back((connection.module_uri, depth + 1));
+          }
+        }
+      }
+      depths
+    });
+
+    module_graph
+      .modules()
+      .map(|module| module.uri.as_str())
+      .filter(|uri| {
+        self
+          .path_prefix
+          .as_ref()
+          .is_none_or(|prefix| uri.starts_with(prefix.as_str()))
+      })
+      .filter(|uri| depths.as_ref().is_none_or(|depths| depths.contains_key(uri)))
+      .collect()
+  }
+}
+
+impl ModuleGraph {
+  /// Renders modules to Graphviz DOT, with edges labeled by the kind of dependency.
+  pub fn to_dot(&self, entries: &[String], options: &DotOptions) -> String {
+    let mut graph = DiGraph::<String, String>::new();
+    let nodes = options
+      .filter_modules(self, entries)
+      .into_iter()
+      .map(|uri| (uri, graph.add_node(uri.to_string())))
+      .collect::<HashMap<_, _>>();
+
+    for module in self.modules() {
+      let from = match nodes.get(module.uri.as_str()) {
+        Some(from) => *from,
+        None => continue,
+      };
+      for connection in self.outgoing_connections(&module.uri) {
+        if let Some(to) = nodes.get(connection.module_uri) {
+          graph.add_edge(from, *to, format!("{:?}", connection.kind()));
+        }
+      }
+    }
+
+    format!("{}", Dot::new(&graph))
+  }
+}
+
+impl ChunkGraph {
+  /// Renders chunks to Graphviz DOT, with an edge to each module placed in the chunk
+  /// and an edge to each chunk it depends on.
+  pub fn to_dot(&self, module_graph: &ModuleGraph, entries: &[String], options: &DotOptions) -> String {
+    let mut graph = DiGraph::<String, &str>::new();
+    let chunk_nodes = self
+      .chunks()
+      .map(|chunk| (chunk.id.as_str(), graph.add_node(format!("chunk {}", chunk.id))))
+      .collect::<HashMap<_, _>>();
+    let mut module_nodes: HashMap<&str, NodeIndex> = HashMap::new();
+
+    let modules = options.filter_modules(module_graph, entries);
+    for chunk in self.chunks() {
+      for uri in modules.iter().filter(|uri| chunk.module_uris.contains(**uri)) {
+        let module_node = *module_nodes
+          .entry(*uri)
+          .or_insert_with(|| graph.add_node(uri.to_string()));
+        graph.add_edge(chunk_nodes[chunk.id.as_str()], module_node, "contains");
+      }
+    }
+
+    for (from, to) in self.relations() {
+      if let (Some(from), Some(to)) = (chunk_nodes.get(from), chunk_nodes.get(to)) {
+        graph.add_edge(*from, *to, "depends on");
+      }
+    }
+
+    format!("{}", Dot::new(&graph))
+  }
+}
@@ -0,0 +1,28 @@
+use crate::{Module, ParseModuleArgs};
+
+/// Built-in module for `.json` files, used when no plugin registers a parser for
+/// `SourceType::Json`.
+#[derive(Debug)]
+pub struct JsonModule {
+  pub uri: String,
+  pub value: serde_json::Value,
+}
+
+impl JsonModule {
+  pub fn parse(args: ParseModuleArgs) -> anyhow::Result<Self> {
+    let source = args.source.try_into_string()?;
+    // The error message of serde_json ends with `at line {} column {}`.
+    let value = serde_json::from_str(&source)
+      .map_err(|err| anyhow::format_err!("invalid JSON in {}: {}", args.uri, err))?;
+    Ok(Self {
+      uri: args.uri.to_string(),
+      value,
+    })
+  }
+}
+
+impl Module for JsonModule {
+  fn render(&self) -> String {
+    format!("module.exports = {};", self.value)
+  }
+}
@@ -18,6 +18,32 @@ mod chunk_graph;
 pub use chunk_graph::*;
 mod utils;
 pub use utils::*;
+mod loader;
+pub use loader::*;
+mod diagnostic;
+pub use diagnostic::*;
+mod json_module;
+pub use json_module::*;
+mod asset_module;
+pub use asset_module::*;
+mod css_module;
+pub use css_module::*;
+mod external_module;
+pub use external_module::*;
+mod empty_module;
+pub use empty_module::*;
+mod package_json;
+pub use package_json::*;
+mod stats;
+pub use stats::*;
+mod module_concatenation;
+pub use module_concatenation::*;
+mod module_explanation;
+pub use module_explanation::*;
+mod graph_dot;
+pub use graph_dot::*;
+#[cfg(test)]
+mod test_utils;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SourceType {
@@ -27,6 +53,26 @@ pub enum SourceType {
   Jsx,
   Tsx,
   Ts,
+  /// Picks `AssetInline` or `AssetResource` by the size of the file.
+  Asset,
+  /// Emitted as a separate file, the module exports its public url.
+  AssetResource,
+  /// Exported as a base64 data uri.
+  AssetInline,
+  /// Exported as the source string of the file.
+  AssetSource,
+  Wasm,
+  /// Module types registered by plugins through `Plugin::register_module_types`.
+  Custom(&'static str),
+}
+
+impl SourceType {
+  pub fn is_asset(&self) -> bool {
+    matches!(
+      self,
+      Self::Asset | Self::AssetResource | Self::AssetInline | Self::AssetSource
+    )
+  }
 }
 
 impl TryFrom<&str> for SourceType {
@@ -40,6 +86,9 @@ impl TryFrom<&str> for SourceType {
       "jsx" => Ok(Self::Jsx),
       "tsx" => Ok(Self::Tsx),
       "ts" => Ok(Self::Ts),
+      "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "ico" | "bmp" | "svg" | "woff"
+      | "woff2" | "ttf" | "otf" | "eot" => Ok(Self::Asset),
+      "wasm" => Ok(Self::Wasm),
       _ => Err(()),
     }
   }
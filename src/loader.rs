@@ -0,0 +1,30 @@
+use std::fmt::Debug;
+
+use crate::{Content, ModuleRule};
+
+#[derive(Debug)]
+pub struct LoaderArgs<'a> {
+  pub uri: &'a str,
+  pub source: Content,
+}
+
+pub trait Loader: Debug + Send + Sync {
+  fn name(&self) -> &str;
+  fn run(&self, args: LoaderArgs) -> anyhow::Result<Content>;
+}
+
+/// Runs the loaders of every matched rule.
+///
+/// Like webpack, the `uses` of the matched rules are concatenated in declaration
+/// order and applied from last to first, so the loaders of the last rule run first.
+pub fn run_loaders(rules: &[&ModuleRule], uri: &str, source: Content) -> anyhow::Result<Content> {
+  rules
+    .iter()
+    .flat_map(|rule| rule.uses.iter())
+    .rev()
+    .try_fold(source, |source, loader| {
+      loader
+        .run(LoaderArgs { uri, source })
+        .map_err(|err| anyhow::format_err!("loader {} failed on {}: {}", loader.name(), uri, err))
+    })
+}
@@ -1,12 +1,25 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use crate::{ModuleDependency, SourceType, Dependency, ModuleGraph, ResolveKind};
+use crate::{ModuleDependency, SourceType, Dependency, ModuleGraph, ResolveKind, Asset, Resource, ResourceData, ConcatenationScope, Diagnostic};
 #[derive(Debug)]
 pub struct ModuleGraphModule {
   pub exec_order: usize,
   pub module: BoxModule,
   pub uri: String,
+  /// Parts of `uri`, the whole uri is the path for modules not loaded from a file.
+  pub resource: Resource,
   pub source_type: SourceType,
   pub dependencies: Vec<Dependency>,
+  /// Files other than `uri` that the module was built from.
+  pub file_dependencies: Vec<String>,
+  /// Exports used by other modules, computed by `ModuleGraph::calc_used_exports`.
+  pub used_exports: UsedExports,
+  /// `sideEffects` of the `package.json` of the module, overriding
+  /// `Module::has_side_effects` when set.
+  pub side_effects: Option<bool>,
+  /// Whether the module ends up in the output, a side effect free module is dropped
+  /// when none of its exports are used.
+  pub included: bool,
 }
 
 impl ModuleGraphModule {
@@ -19,15 +32,49 @@ impl ModuleGraphModule {
       Self {
         exec_order: usize::MAX,
         module,
+        resource: Resource {
+          path: uri.clone(),
+          ..Default::default()
+        },
         uri,
         source_type,
         dependencies,
+        file_dependencies: vec![],
+        used_exports: UsedExports::default(),
+        side_effects: None,
+        included: true,
       }
     }
     pub fn id(&self) -> &str {
       self.uri.as_str()
     }
 
+    /// Path, query and fragment of the uri. Modules of the same path with different
+    /// queries are different modules.
+    pub fn resource(&self) -> ResourceData<'_> {
+      self.resource.as_data()
+    }
+
+    pub fn has_side_effects(&self) -> bool {
+      self
+        .side_effects
+        .unwrap_or_else(|| self.module.has_side_effects())
+    }
+
+    pub fn render(&self) -> String {
+      self.module.render_with_used_exports(&self.used_exports)
+    }
+
+    /// Code of the module in a js chunk, see `Chunk::render_js_modules`. Css is
+    /// rendered to css chunks instead, only its `Module::render_js_exports` are left
+    /// for js importers.
+    pub fn render_js(&self) -> String {
+      match self.source_type {
+        SourceType::Css => self.module.render_js_exports().unwrap_or_default(),
+        _ => self.render(),
+      }
+    }
+
     pub fn depended_modules<'a>(&self, module_graph: &'a ModuleGraph) -> Vec<&'a ModuleGraphModule> {
       self
         .dependencies
@@ -42,6 +89,136 @@ pub trait Module: Debug + Send + Sync {
   fn dependencies(&mut self) -> Vec<ModuleDependency> {
     vec![]
   }
+  /// Namespace import the js chunk renderer must place at the top level of the chunk,
+  /// the rendered module refers to it by `HoistedImport::local`.
+  fn hoisted_import(&self) -> Option<HoistedImport> {
+    None
+  }
+  /// Whether the module exports a promise of its exports, which importers must await
+  /// before using them.
+  fn is_async(&self) -> bool {
+    false
+  }
+  /// Warnings found while parsing, the build goes on.
+  fn diagnostics(&self) -> Vec<Diagnostic> {
+    vec![]
+  }
+  /// Files emitted by the module itself, in addition to the rendered chunks.
+  fn assets(&self) -> Vec<Asset> {
+    vec![]
+  }
+  /// Code representing the module in js chunks when it is not a js module itself,
+  /// e.g. the mapping of class names exported by a css module. Used by
+  /// `ModuleGraphModule::render_js`.
+  fn render_js_exports(&self) -> Option<String> {
+    None
+  }
+  /// Url the module is referred to by `url()` in css.
+  fn url(&self) -> Option<String> {
+    None
+  }
+  /// Names exported by the module, `default` included.
+  fn exports(&self) -> Vec<String> {
+    vec![]
+  }
+  /// Names the module imports through `dependency`, given its own exports which are
+  /// used. A re-export only imports what is used, e.g. `export * from './x'` imports
+  /// the used names the module does not declare itself.
+  fn imported_names(&self, _dependency: &ModuleDependency, _used_exports: &UsedExports) -> ImportedNames {
+    ImportedNames::All
+  }
+  fn has_side_effects(&self) -> bool {
+    true
+  }
+  /// Renders the module without the exports missing from `used_exports`.
+  fn render_with_used_exports(&self, _used_exports: &UsedExports) -> String {
+    self.render()
+  }
+  /// Whether the module is a strict ES module, which is required for it to be merged
+  /// into a concatenated scope.
+  fn is_esm(&self) -> bool {
+    false
+  }
+  /// Names of the bindings declared at the top level of the module.
+  fn top_level_bindings(&self) -> Vec<String> {
+    vec![]
+  }
+  /// Exports of the module mapped to the top-level binding they refer to.
+  fn export_bindings(&self) -> HashMap<String, String> {
+    Default::default()
+  }
+  /// Why an ES module can not be merged into a concatenated scope, e.g. it uses `eval`
+  /// or `module.exports`.
+  fn concatenation_bailout(&self) -> Option<String> {
+    None
+  }
+  /// Renders the module without its wrapper, with bindings renamed as in `scope`,
+  /// and without the exports missing from `scope.used_exports`.
+  fn render_concatenated(&self, scope: &ConcatenationScope) -> String {
+    self.render_with_used_exports(scope.used_exports)
+  }
+}
+
+/// `import * as <local> from <request>` at the top level of a js chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoistedImport {
+  pub request: String,
+  pub local: String,
+}
+
+/// Bindings imported through a dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportedNames {
+  /// Namespace imports, `require`, re-exports of a module whose exports are all used
+  /// and anything that can not be analyzed.
+  All,
+  Names(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsedExports {
+  All,
+  Names(HashSet<String>),
+}
+
+impl Default for UsedExports {
+  fn default() -> Self {
+    Self::Names(Default::default())
+  }
+}
+
+impl UsedExports {
+  pub fn is_empty(&self) -> bool {
+    match self {
+      Self::All => false,
+      Self::Names(names) => names.is_empty(),
+    }
+  }
+
+  pub fn is_used(&self, name: &str) -> bool {
+    match self {
+      Self::All => true,
+      Self::Names(names) => names.contains(name),
+    }
+  }
+
+  /// Adds `imported` and returns whether anything changed.
+  pub fn extend(&mut self, imported: &ImportedNames) -> bool {
+    match (&mut *self, imported) {
+      (Self::All, _) => false,
+      (Self::Names(_), ImportedNames::All) => {
+        *self = Self::All;
+        true
+      }
+      (Self::Names(names), ImportedNames::Names(imported)) => {
+        let mut changed = false;
+        for name in imported {
+          changed |= names.insert(name.clone());
+        }
+        changed
+      }
+    }
+  }
 }
 
 pub type BoxModule = Box<dyn Module>;
\ No newline at end of file
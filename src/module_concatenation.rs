@@ -0,0 +1,348 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Compilation, ConcatenationBailout, ModuleGraph, ModuleGraphModule, ResolveKind, UsedExports};
+
+/// Modules of a chunk merged into a single scope, rendered without the per-module
+/// wrapper.
+#[derive(Debug, Clone)]
+pub struct ConcatenatedModule {
+  /// The only module of the scope which can be imported from outside of it.
+  pub root: String,
+  /// Uris of the merged modules in evaluation order, `root` included.
+  pub modules: Vec<String>,
+  /// Top-level bindings of each module mapped to their names in the merged scope.
+  pub bindings: HashMap<String, HashMap<String, String>>,
+}
+
+/// What the specifiers of a module refer to in a concatenated scope.
+pub struct ConcatenationScope<'a> {
+  /// Top-level bindings of the module mapped to their names in the merged scope.
+  pub bindings: &'a HashMap<String, String>,
+  /// Specifiers of the module mapped to the module they import.
+  pub imports: HashMap<&'a str, ImportedModule<'a>>,
+  /// Exports of the module used by other modules, the others can be left out as in
+  /// `Module::render_with_used_exports`.
+  pub used_exports: &'a UsedExports,
+}
+
+pub enum ImportedModule<'a> {
+  /// A module of the same scope, its exports mapped to their names in the scope.
+  Concatenated(HashMap<String, String>),
+  /// A module outside of the scope, accessed through the runtime by its uri.
+  External(&'a str),
+}
+
+impl ConcatenatedModule {
+  pub fn render(&self, module_graph: &ModuleGraph) -> String {
+    let empty = HashMap::new();
+    self
+      .modules
+      .iter()
+      .filter_map(|uri| module_graph.module_by_uri(uri))
+      .map(|module| {
+        let imports = module
+          .dependencies
+          .iter()
+          .filter_map(|dep| {
+            let dep_module = module_graph.module_by_dependency(dep)?;
+            let imported = if self.modules.contains(&dep_module.uri) {
+              ImportedModule::Concatenated(self.export_names(dep_module))
+            } else {
+              ImportedModule::External(dep_module.uri.as_str())
+            };
+            Some((dep.specifier.as_str(), imported))
+          })
+          .collect();
+        let scope = ConcatenationScope {
+          bindings: self.bindings.get(&module.uri).unwrap_or(&empty),
+          imports,
+          used_exports: &module.used_exports,
+        };
+        module.module.render_concatenated(&scope)
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  fn export_names(&self, module: &ModuleGraphModule) -> HashMap<String, String> {
+    let bindings = self.bindings.get(&module.uri);
+    module
+      .module
+      .export_bindings()
+      .into_iter()
+      .map(|(export, local)| {
+        let name = bindings
+          .and_then(|bindings| bindings.get(&local))
+          .cloned()
+          .unwrap_or(local);
+        (export, name)
+      })
+      .collect()
+  }
+}
+
+/// Merges strictly ES modules of each chunk into concatenated scopes.
+///
+/// A module joins the scope of one of its importers when it is an ES module without
+/// a bailout reason, and all of its importers are in that scope and import it
+/// statically. Entry modules are never merged into the scope of another module.
+/// Modules which end up in no scope keep their wrapper, with the reason recorded in
+/// `Stats::concatenation_bailouts`.
+pub fn concatenate_modules(compilation: &mut Compilation) {
+  let module_graph = &compilation.module_graph;
+
+  let entry_modules = module_graph
+    .modules()
+    .filter(|module| {
+      module_graph
+        .incoming_connections(&module.uri)
+        .iter()
+        .any(|connection| connection.original_uri().is_none())
+    })
+    .map(|module| module.uri.as_str())
+    .collect::<HashSet<_>>();
+  let importers = module_graph
+    .modules()
+    .map(|module| {
+      let importers = module_graph
+        .incoming_connections(&module.uri)
+        .into_iter()
+        .filter_map(|connection| Some((connection.original_uri()?, connection.kind())))
+        .collect::<Vec<_>>();
+      (module.uri.as_str(), importers)
+    })
+    .collect::<HashMap<_, _>>();
+
+  let root_bailout = |module: &ModuleGraphModule| -> Option<String> {
+    if !module.module.is_esm() {
+      return Some(String::from("not an ES module"));
+    }
+    module.module.concatenation_bailout()
+  };
+
+  let mut bailouts = vec![];
+  let mut concatenated_by_chunk = vec![];
+  for chunk in compilation.chunk_graph.chunks() {
+    let ordered = chunk.ordered_module(module_graph);
+    let mut grouped: HashSet<&str> = HashSet::new();
+    let mut concatenated_modules = vec![];
+    // Importers are evaluated after their dependencies, so roots are picked from the end.
+    for root in ordered.iter().rev() {
+      if grouped.contains(root.uri.as_str()) || root_bailout(root).is_some() {
+        continue;
+      }
+      let mut group = vec![root.uri.as_str()];
+      let mut changed = true;
+      while changed {
+        changed = false;
+        for index in 0..group.len() {
+          let member = module_graph.module_by_uri(group[index]).unwrap();
+          for dep_module in member.depended_modules(module_graph) {
+            let uri = dep_module.uri.as_str();
+            let joinable = chunk.module_uris.contains(uri)
+              && !grouped.contains(uri)
+              && !group.contains(&uri)
+              && !entry_modules.contains(uri)
+              && root_bailout(dep_module).is_none()
+              && importers.get(uri).is_some_and(|importers| {
+                importers
+                  .iter()
+                  .all(|(importer, kind)| group.contains(importer) && matches!(kind, ResolveKind::Import))
+              });
+            if joinable {
+              group.push(uri);
+              changed = true;
+            }
+          }
+        }
+      }
+      if group.len() > 1 {
+        grouped.extend(group.iter().copied());
+        concatenated_modules.push(concatenated_module(module_graph, group));
+      }
+    }
+
+    for module in &ordered {
+      if grouped.contains(module.uri.as_str()) {
+        continue;
+      }
+      let reason = root_bailout(module).unwrap_or_else(|| {
+        match importers.get(module.uri.as_str()) {
+          Some(importers) if !importers.is_empty() && entry_modules.contains(module.uri.as_str()) => {
+            String::from("entry module")
+          }
+          Some(importers) if importers.iter().any(|(_, kind)| matches!(kind, ResolveKind::DynamicImport)) => {
+            String::from("imported by import()")
+          }
+          Some(importers) if importers.iter().any(|(_, kind)| !matches!(kind, ResolveKind::Import)) => {
+            String::from("imported by require() or another non-ESM dependency")
+          }
+          _ => String::from("no ES module imported only by it could be merged"),
+        }
+      });
+      bailouts.push(ConcatenationBailout {
+        uri: module.uri.clone(),
+        reason,
+      });
+    }
+    concatenated_by_chunk.push((chunk.id.clone(), concatenated_modules));
+  }
+
+  for (chunk_id, concatenated_modules) in concatenated_by_chunk {
+    if let Some(chunk) = compilation.chunk_graph.chunk_by_id_mut(&chunk_id) {
+      chunk.concatenated_modules = concatenated_modules;
+    }
+  }
+  bailouts.sort_by(|a, b| a.uri.cmp(&b.uri));
+  compilation.stats.concatenation_bailouts = bailouts;
+}
+
+/// Renames colliding top-level bindings, the module evaluated first keeps the name.
+fn concatenated_module(module_graph: &ModuleGraph, group: Vec<&str>) -> ConcatenatedModule {
+  let root = group[0].to_string();
+  let mut modules = group
+    .into_iter()
+    .filter_map(|uri| module_graph.module_by_uri(uri))
+    .collect::<Vec<_>>();
+  modules.sort_by_key(|module| module.exec_order);
+
+  let mut used_names: HashSet<String> = HashSet::new();
+  let bindings = modules
+    .iter()
+    .map(|module| {
+      let renamed = module
+        .module
+        .top_level_bindings()
+        .into_iter()
+        .map(|binding| {
+          let mut name = binding.clone();
+          let mut index = 1;
+          while used_names.contains(&name) {
+            name = format!("{}_{}", binding, index);
+            index += 1;
+          }
+          used_names.insert(name.clone());
+          (binding, name)
+        })
+        .collect();
+      (module.uri.clone(), renamed)
+    })
+    .collect();
+
+  ConcatenatedModule {
+    root,
+    modules: modules.iter().map(|module| module.uri.clone()).collect(),
+    bindings,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::split_chunker::split_code;
+  use crate::test_utils::{build_compilation, TestModule};
+
+  fn concatenate(entries: &[&str], modules: Vec<(&str, TestModule)>) -> Compilation {
+    let mut compilation = build_compilation(entries, modules);
+    split_code(&mut compilation);
+    concatenate_modules(&mut compilation);
+    compilation
+  }
+
+  fn bailout<'a>(compilation: &'a Compilation, uri: &str) -> Option<&'a str> {
+    compilation
+      .stats
+      .concatenation_bailouts
+      .iter()
+      .find(|bailout| bailout.uri == uri)
+      .map(|bailout| bailout.reason.as_str())
+  }
+
+  #[test]
+  fn merges_modules_imported_only_from_the_scope() {
+    let compilation = concatenate(
+      &["/a.js"],
+      vec![
+        ("/a.js", TestModule::new("a").esm(&["x"]).with_dependency("/b.js", ResolveKind::Import)),
+        ("/b.js", TestModule::new("b").esm(&["x"]).with_dependency("/c.js", ResolveKind::Import)),
+        ("/c.js", TestModule::new("c").esm(&["y"])),
+      ],
+    );
+
+    let chunk = compilation.chunk_graph.chunks().next().unwrap();
+    assert_eq!(chunk.concatenated_modules.len(), 1);
+    let concatenated = &chunk.concatenated_modules[0];
+    assert_eq!(concatenated.root, "/a.js");
+    assert_eq!(concatenated.modules, ["/c.js", "/b.js", "/a.js"]);
+    // The module evaluated first keeps the name.
+    assert_eq!(
+      concatenated.render(&compilation.module_graph),
+      "c [y=y]  used=*\nb [x=x] /c.js:{y=y} used=*\na [x=x_1] /b.js:{x=x} used=*"
+    );
+    assert!(compilation.stats.concatenation_bailouts.is_empty());
+  }
+
+  #[test]
+  fn renders_merged_modules_with_their_used_exports() {
+    let compilation = concatenate(
+      &["/a.js"],
+      vec![
+        ("/a.js", TestModule::new("a").esm(&[]).importing("/b.js", &["x"])),
+        ("/b.js", TestModule::new("b").esm(&["x", "y"])),
+      ],
+    );
+
+    let chunk = compilation.chunk_graph.chunks().next().unwrap();
+    assert_eq!(
+      chunk.concatenated_modules[0].render(&compilation.module_graph),
+      "b [x=x,y=y]  used=x\na [] /b.js:{x=x,y=y} used=*"
+    );
+  }
+
+  #[test]
+  fn records_bailout_reasons() {
+    let compilation = concatenate(
+      &["/a.js"],
+      vec![
+        (
+          "/a.js",
+          TestModule::new("a")
+            .esm(&[])
+            .with_dependency("/cjs.js", ResolveKind::Import)
+            .with_dependency("/required.js", ResolveKind::Require)
+            .with_dependency("/lazy.js", ResolveKind::DynamicImport),
+        ),
+        ("/cjs.js", TestModule::new("cjs")),
+        ("/required.js", TestModule::new("required").esm(&[])),
+        ("/lazy.js", TestModule::new("lazy").esm(&[])),
+      ],
+    );
+
+    assert_eq!(bailout(&compilation, "/cjs.js"), Some("not an ES module"));
+    assert_eq!(
+      bailout(&compilation, "/required.js"),
+      Some("imported by require() or another non-ESM dependency")
+    );
+    assert_eq!(
+      bailout(&compilation, "/a.js"),
+      Some("no ES module imported only by it could be merged")
+    );
+  }
+
+  #[test]
+  fn keeps_entry_modules_out_of_other_scopes() {
+    let compilation = concatenate(
+      &["/a.js", "/b.js"],
+      vec![
+        ("/a.js", TestModule::new("a").esm(&[]).with_dependency("/b.js", ResolveKind::Import)),
+        ("/b.js", TestModule::new("b").esm(&[])),
+      ],
+    );
+
+    assert!(compilation
+      .chunk_graph
+      .chunks()
+      .all(|chunk| chunk.concatenated_modules.is_empty()));
+    assert_eq!(bailout(&compilation, "/b.js"), Some("entry module"));
+  }
+}
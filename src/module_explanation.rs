@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{ChunkGraph, ModuleGraph, ModuleGraphConnection, ResolveKind};
+
+/// Why a module ends up in the output, see `ModuleGraph::explain`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleExplanation {
+  pub uri: String,
+  /// Shortest path from each entry reaching the module, in entry order.
+  pub import_paths: Vec<ImportPath>,
+  /// Chunks the module is placed in, in chunk order.
+  pub chunks: Vec<ChunkPlacement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportPath {
+  /// Uri of the entry module.
+  pub entry: String,
+  /// Dependencies followed from the entry, empty when the module is the entry itself.
+  pub steps: Vec<ImportStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportStep {
+  pub importer: String,
+  pub specifier: String,
+  pub kind: ResolveKind,
+  /// Uri of the module `specifier` resolves to.
+  pub module_uri: String,
+}
+
+impl From<ModuleGraphConnection<'_>> for ImportStep {
+  fn from(connection: ModuleGraphConnection<'_>) -> Self {
+    Self {
+      importer: connection.original_uri().unwrap_or_default().to_string(),
+      specifier: connection.dependency.specifier.clone(),
+      kind: connection.kind(),
+      module_uri: connection.module_uri.to_string(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkPlacement {
+  pub chunk_id: String,
+  pub reason: ChunkPlacementReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkPlacementReason {
+  /// The entry of the chunk statically imports the module, and no other chunk does.
+  Direct,
+  /// The module is statically imported by the entries of other chunks as well, which
+  /// reuse it from this chunk.
+  Shared,
+  /// The entry of the chunk is loaded by `import()`.
+  Async,
+}
+
+impl ModuleGraph {
+  /// Explains how the module is reached from `entries` and why it is placed in its
+  /// chunks. Returns `None` when the module is not in the graph.
+  pub fn explain(&self, uri: &str, entries: &[String], chunk_graph: &ChunkGraph) -> Option<ModuleExplanation> {
+    let module = self.module_by_uri(uri)?;
+
+    let import_paths = entries
+      .iter()
+      .filter_map(|entry| {
+        Some(ImportPath {
+          entry: entry.clone(),
+          steps: self.shortest_import_path(entry, &module.uri)?,
+        })
+      })
+      .collect();
+
+    let chunks = chunk_graph
+      .chunks()
+      .filter(|chunk| chunk.module_uris.contains(&module.uri))
+      .map(|chunk| {
+        let is_async = self
+          .incoming_connections(&chunk.entry_uri)
+          .iter()
+          .any(|connection| matches!(connection.kind(), ResolveKind::DynamicImport));
+        let is_shared = chunk_graph.chunks().any(|other| {
+          other.id != chunk.id && self.statically_reaches(&other.entry_uri, &module.uri)
+        });
+        let reason = if is_async {
+          ChunkPlacementReason::Async
+        } else if is_shared {
+          ChunkPlacementReason::Shared
+        } else {
+          ChunkPlacementReason::Direct
+        };
+        ChunkPlacement {
+          chunk_id: chunk.id.clone(),
+          reason,
+        }
+      })
+      .collect();
+
+    Some(ModuleExplanation {
+      uri: module.uri.clone(),
+      import_paths,
+      chunks,
+    })
+  }
+
+  /// Breadth first search from `from` following every kind of dependency.
+  fn shortest_import_path(&self, from: &str, to: &str) -> Option<Vec<ImportStep>> {
+    let mut reached_by: HashMap<&str, ModuleGraphConnection> = HashMap::new();
+    let mut visited = HashSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(uri) = queue.pop_front() {
+      if uri == to {
+        let mut steps = vec![];
+        let mut current = uri;
+        while let Some(connection) = reached_by.get(current) {
+          steps.push(ImportStep::from(*connection));
+          current = connection.original_uri().unwrap_or(from);
+        }
+        steps.reverse();
+        return Some(steps);
+      }
+      for connection in self.outgoing_connections(uri) {
+        if visited.insert(connection.module_uri) {
+          reached_by.insert(connection.module_uri, connection);
+          queue.push_back(connection.module_uri);
+        }
+      }
+    }
+    None
+  }
+
+  fn statically_reaches(&self, from: &str, to: &str) -> bool {
+    let mut visited = HashSet::from([from]);
+    let mut stack = vec![from];
+    while let Some(uri) = stack.pop() {
+      if uri == to {
+        return true;
+      }
+      if let Some(module) = self.module_by_uri(uri) {
+        for dep_module in module.depended_modules(self) {
+          if visited.insert(dep_module.uri.as_str()) {
+            stack.push(dep_module.uri.as_str());
+          }
+        }
+      }
+    }
+    false
+  }
+}
@@ -1,10 +1,33 @@
-use std::collections::HashMap;
-use crate::{Dependency, ModuleGraphModule};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::{Dependency, ResolveKind, ImportedNames, ModuleDependency, ModuleGraphModule, UsedExports};
+
+/// A dependency resolved to the module it refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleGraphConnection<'a> {
+  pub dependency: &'a Dependency,
+  /// Uri of the module the dependency resolves to.
+  pub module_uri: &'a str,
+}
+
+impl<'a> ModuleGraphConnection<'a> {
+  /// Uri of the module declaring the dependency, `None` for entries.
+  pub fn original_uri(&self) -> Option<&'a str> {
+    self.dependency.importer.as_deref()
+  }
+
+  pub fn kind(&self) -> ResolveKind {
+    self.dependency.kind
+  }
+}
 
 #[derive(Debug, Default)]
 pub struct ModuleGraph {
-  uri_to_module: HashMap<String, ModuleGraphModule>,
+  /// Keyed by uri, so that modules are iterated in the same order on every build.
+  uri_to_module: BTreeMap<String, ModuleGraphModule>,
   dependency_to_module_uri: HashMap<Dependency, String>,
+  /// Dependencies resolving to each module, ordered so that lookups do not depend on
+  /// the order modules finish building.
+  module_uri_to_incoming: HashMap<String, BTreeSet<Dependency>>,
   // id_to_uri: hashbrown::HashMap<String, String>,
 }
 
@@ -14,9 +37,53 @@ impl ModuleGraph {
     }
 
     pub fn add_dependency(&mut self, dependency: Dependency, uri: String) {
+        if let Some(previous) = self.dependency_to_module_uri.get(&dependency) {
+            if let Some(incoming) = self.module_uri_to_incoming.get_mut(previous) {
+                incoming.remove(&dependency);
+            }
+        }
+        self
+            .module_uri_to_incoming
+            .entry(uri.clone())
+            .or_default()
+            .insert(dependency.clone());
         self.dependency_to_module_uri.insert(dependency, uri);
     }
 
+    /// Connections resolving to the module, entries included.
+    pub fn incoming_connections(&self, uri: &str) -> Vec<ModuleGraphConnection<'_>> {
+        match self.module_uri_to_incoming.get_key_value(uri) {
+            Some((module_uri, incoming)) => incoming
+                .iter()
+                .map(|dependency| ModuleGraphConnection {
+                    dependency,
+                    module_uri: module_uri.as_str(),
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Connections from the dependencies of the module, in the order they are declared.
+    /// Dependencies which failed to resolve are left out.
+    pub fn outgoing_connections(&self, uri: &str) -> Vec<ModuleGraphConnection<'_>> {
+        match self.uri_to_module.get(uri) {
+            Some(module) => module
+                .dependencies
+                .iter()
+                .filter_map(|dependency| {
+                    let (dependency, module_uri) =
+                        self.dependency_to_module_uri.get_key_value(dependency)?;
+                    Some(ModuleGraphConnection {
+                        dependency,
+                        module_uri: module_uri.as_str(),
+                    })
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+
     pub fn uri_by_dependency(&self, dep: &Dependency) -> Option<&str> {
         let uri = self.dependency_to_module_uri.get(dep)?;
         Some(uri.as_str())
@@ -38,4 +105,142 @@ impl ModuleGraph {
     pub fn modules(&self) -> impl Iterator<Item = &ModuleGraphModule> {
         self.uri_to_module.values()
     }
-}
\ No newline at end of file
+
+    /// Computes `used_exports` and `included` of every module reachable from `entries`,
+    /// whose exports are all used.
+    ///
+    /// Imports of a module only count once the module is included, so a side effect
+    /// free module with no used export does not keep its own dependencies alive. The
+    /// imports of a module are computed again whenever more of its exports are used,
+    /// so re-exports only keep the used exports of the re-exported modules.
+    pub fn calc_used_exports(&mut self, entries: &[String]) {
+        self.uri_to_module.values_mut().for_each(|module| {
+            module.used_exports = UsedExports::default();
+            module.included = false;
+        });
+        let mut queue = vec![];
+        for entry in entries {
+            if let Some(module) = self.uri_to_module.get_mut(entry) {
+                module.used_exports = UsedExports::All;
+                if !module.included {
+                    module.included = true;
+                    queue.push(entry.clone());
+                }
+            }
+        }
+        while let Some(uri) = queue.pop() {
+            let imports = match self.uri_to_module.get(&uri) {
+                Some(module) => module
+                    .dependencies
+                    .iter()
+                    .filter_map(|dep| {
+                        let dep_uri = self.dependency_to_module_uri.get(dep)?.clone();
+                        let imported = match dep.kind {
+                            ResolveKind::DynamicImport => ImportedNames::All,
+                            _ => module
+                                .module
+                                .imported_names(&ModuleDependency::from(dep), &module.used_exports),
+                        };
+                        Some((dep_uri, imported))
+                    })
+                    .collect::<Vec<_>>(),
+                None => continue,
+            };
+            for (dep_uri, imported) in imports {
+                let dep_module = match self.uri_to_module.get_mut(&dep_uri) {
+                    Some(module) => module,
+                    None => continue,
+                };
+                let changed = dep_module.used_exports.extend(&imported);
+                if dep_module.included {
+                    if changed {
+                        queue.push(dep_uri);
+                    }
+                } else if dep_module.has_side_effects() || !dep_module.used_exports.is_empty() {
+                    dep_module.included = true;
+                    queue.push(dep_uri);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::test_utils::{build_compilation, used_names, TestModule};
+  use crate::{Compilation, ResolveKind};
+
+  /// `None` when the module is dropped, otherwise its used exports.
+  fn used(compilation: &Compilation, uri: &str) -> Option<String> {
+    let module = compilation.module_graph.module_by_uri(uri).unwrap();
+    module.included.then(|| used_names(&module.used_exports))
+  }
+
+  #[test]
+  fn keeps_only_used_exports_of_a_side_effect_free_barrel() {
+    let compilation = build_compilation(
+      &["/a.js"],
+      vec![
+        (
+          "/a.js",
+          // The barrel is visited before `c` adds `y` to its used exports.
+          TestModule::new("a")
+            .with_dependency("/c.js", ResolveKind::Import)
+            .importing("/barrel.js", &["x"]),
+        ),
+        ("/c.js", TestModule::new("c").importing("/barrel.js", &["y"])),
+        (
+          "/barrel.js",
+          TestModule::new("barrel")
+            .side_effect_free()
+            .reexporting("/x.js", None)
+            .reexporting("/y.js", Some(&["y"]))
+            .reexporting("/z.js", Some(&["z"])),
+        ),
+        ("/x.js", TestModule::new("x").esm(&["x", "w"]).side_effect_free()),
+        ("/y.js", TestModule::new("y").esm(&["y"]).side_effect_free()),
+        ("/z.js", TestModule::new("z").esm(&["z"]).side_effect_free()),
+      ],
+    );
+
+    assert_eq!(used(&compilation, "/barrel.js").as_deref(), Some("x,y"));
+    assert_eq!(used(&compilation, "/x.js").as_deref(), Some("x"));
+    assert_eq!(used(&compilation, "/y.js").as_deref(), Some("y"));
+    assert_eq!(used(&compilation, "/z.js"), None);
+  }
+
+  #[test]
+  fn drops_unused_side_effect_free_leaves_and_their_dependencies() {
+    let compilation = build_compilation(
+      &["/a.js"],
+      vec![
+        ("/a.js", TestModule::new("a").importing("/leaf.js", &[])),
+        (
+          "/leaf.js",
+          TestModule::new("leaf")
+            .esm(&["leaf"])
+            .side_effect_free()
+            .with_dependency("/polyfill.js", ResolveKind::Import),
+        ),
+        ("/polyfill.js", TestModule::new("polyfill")),
+      ],
+    );
+
+    assert_eq!(used(&compilation, "/a.js").as_deref(), Some("*"));
+    assert_eq!(used(&compilation, "/leaf.js"), None);
+    assert_eq!(used(&compilation, "/polyfill.js"), None);
+  }
+
+  #[test]
+  fn uses_every_export_of_dynamically_imported_modules() {
+    let compilation = build_compilation(
+      &["/a.js"],
+      vec![
+        ("/a.js", TestModule::new("a").with_dependency("/lazy.js", ResolveKind::DynamicImport)),
+        ("/lazy.js", TestModule::new("lazy").esm(&["x"]).side_effect_free()),
+      ],
+    );
+
+    assert_eq!(used(&compilation, "/lazy.js").as_deref(), Some("*"));
+  }
+}
@@ -1,8 +1,15 @@
-use crate::EntryItem;
-use std::collections::HashMap;
+use crate::{EntryItem, Target, ModuleOptions, OutputOptions, ExternalItem, OptimizationOptions};
+use indexmap::IndexMap;
 
 #[derive(Debug, Clone, Default)]
 pub struct ComplierOptions {
-  pub entries: HashMap<String, EntryItem>,
-  pub root: String
-}
\ No newline at end of file
+  /// Entries by name, in declaration order.
+  pub entries: IndexMap<String, EntryItem>,
+  pub root: String,
+  pub target: Target,
+  pub module: ModuleOptions,
+  pub output: OutputOptions,
+  /// Requests matching any item are not bundled, see `External`.
+  pub externals: Vec<ExternalItem>,
+  pub optimization: OptimizationOptions,
+}
@@ -0,0 +1,75 @@
+use std::{fmt::Debug, sync::Arc};
+
+use regex::Regex;
+
+use crate::ResolveKind;
+
+/// How an external request is referenced at runtime.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum External {
+  /// A global variable, e.g. `React`.
+  Global(String),
+  /// `require(...)` of the given request.
+  CommonJs(String),
+  /// A static `import` of the given request, hoisted to the top level of the chunk,
+  /// see `Module::hoisted_import`.
+  Module(String),
+  /// A script loaded from `url`, which exposes `global`. The module exports a promise,
+  /// see `Module::is_async`.
+  Script { url: String, global: String },
+}
+
+impl External {
+  fn with_request(&self, request: &str) -> Self {
+    let replace = |value: &String| value.replace("[request]", request);
+    match self {
+      External::Global(name) => External::Global(replace(name)),
+      External::CommonJs(name) => External::CommonJs(replace(name)),
+      External::Module(name) => External::Module(replace(name)),
+      External::Script { url, global } => External::Script {
+        url: replace(url),
+        global: replace(global),
+      },
+    }
+  }
+}
+
+pub struct ExternalArgs<'a> {
+  pub importer: Option<&'a str>,
+  pub specifier: &'a str,
+  pub kind: ResolveKind,
+}
+
+pub type ExternalCallback = Arc<dyn Fn(&ExternalArgs) -> Option<External> + Send + Sync>;
+
+#[derive(Clone)]
+pub enum ExternalItem {
+  Exact(String, External),
+  /// `[request]` in the external is replaced by the matched specifier.
+  Regex(Regex, External),
+  Callback(ExternalCallback),
+}
+
+impl ExternalItem {
+  pub fn external(&self, args: &ExternalArgs) -> Option<External> {
+    match self {
+      ExternalItem::Exact(specifier, external) => {
+        (specifier == args.specifier).then(|| external.clone())
+      }
+      ExternalItem::Regex(regex, external) => regex
+        .is_match(args.specifier)
+        .then(|| external.with_request(args.specifier)),
+      ExternalItem::Callback(callback) => callback(args),
+    }
+  }
+}
+
+impl Debug for ExternalItem {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Exact(specifier, external) => f.debug_tuple("Exact").field(specifier).field(external).finish(),
+      Self::Regex(regex, external) => f.debug_tuple("Regex").field(regex).field(external).finish(),
+      Self::Callback(_) => f.debug_tuple("Callback").finish(),
+    }
+  }
+}
@@ -3,4 +3,12 @@ pub use entry::*;
 mod complier_options;
 pub use complier_options::*;
 mod optimization;
-pub use optimization::*;
\ No newline at end of file
+pub use optimization::*;
+mod target;
+pub use target::*;
+mod module;
+pub use module::*;
+mod output;
+pub use output::*;
+mod externals;
+pub use externals::*;
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::{Loader, ResourceData, SourceType};
+
+#[derive(Debug, Clone, Default)]
+pub struct ModuleOptions {
+  pub rules: Vec<ModuleRule>,
+  pub asset: AssetOptions,
+  pub css: CssOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct CssOptions {
+  /// Css files matching this condition are css modules with locally scoped class names.
+  pub modules: RuleCondition,
+  /// Generated class names of css modules, supports `[name]`, `[local]`, `[hash]`
+  /// and `[hash:<length>]`.
+  pub local_ident_name: String,
+}
+
+impl Default for CssOptions {
+  fn default() -> Self {
+    Self {
+      modules: RuleCondition::Regex(Regex::new(r"\.module\.css$").unwrap()),
+      local_ident_name: String::from("[name]__[local]__[hash:5]"),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetOptions {
+  /// `SourceType::Asset` files up to this size in bytes are inlined.
+  pub data_url_max_size: usize,
+  /// Filename of emitted resources, supports `[name]`, `[hash]` and `[ext]`.
+  pub filename: String,
+}
+
+impl Default for AssetOptions {
+  fn default() -> Self {
+    Self {
+      data_url_max_size: 8096,
+      filename: String::from("[hash][ext]"),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub enum RuleCondition {
+  Regex(Regex),
+  /// Matches uris starting with the given path.
+  Path(String),
+}
+
+impl RuleCondition {
+  pub fn matches(&self, value: &str) -> bool {
+    match self {
+      RuleCondition::Regex(regex) => regex.is_match(value),
+      RuleCondition::Path(path) => value.starts_with(path.as_str()),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModuleRule {
+  pub test: Option<RuleCondition>,
+  pub include: Vec<RuleCondition>,
+  pub exclude: Vec<RuleCondition>,
+  /// Matched against the query of the uri, without the leading `?`.
+  pub resource_query: Option<RuleCondition>,
+  /// Matched against the fragment of the uri, without the leading `#`.
+  pub resource_fragment: Option<RuleCondition>,
+  pub uses: Vec<Arc<dyn Loader>>,
+  /// Overrides the source type inferred from the extension.
+  pub module_type: Option<SourceType>,
+}
+
+impl ModuleRule {
+  pub fn matches(&self, resource: &ResourceData) -> bool {
+    let path = resource.path;
+    self.test.as_ref().is_none_or(|test| test.matches(path))
+      && (self.include.is_empty() || self.include.iter().any(|cond| cond.matches(path)))
+      && !self.exclude.iter().any(|cond| cond.matches(path))
+      && self
+        .resource_query
+        .as_ref()
+        .is_none_or(|cond| cond.matches(resource.query_value()))
+      && self
+        .resource_fragment
+        .as_ref()
+        .is_none_or(|cond| cond.matches(resource.fragment_value()))
+  }
+}
@@ -1,5 +1,11 @@
 use std::str::FromStr;
 
+#[derive(Debug, Clone, Default)]
+pub struct OptimizationOptions {
+  /// Merge ES modules of a chunk into a single scope, see `concatenate_modules`.
+  pub concatenate_modules: bool,
+}
+
 #[derive(Copy, Clone)]
 pub enum ChunkIdAlgo {
   Named,
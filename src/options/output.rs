@@ -0,0 +1,16 @@
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+  /// Directory assets are emitted to, relative to `root` unless absolute.
+  pub path: String,
+  /// Prefix of urls referring to emitted assets.
+  pub public_path: String,
+}
+
+impl Default for OutputOptions {
+  fn default() -> Self {
+    Self {
+      path: String::from("dist"),
+      public_path: String::from("/"),
+    }
+  }
+}
@@ -0,0 +1,24 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+  #[default]
+  Web,
+  Node,
+}
+
+impl Target {
+  pub fn is_web(&self) -> bool {
+    matches!(self, Self::Web)
+  }
+
+  pub fn is_node(&self) -> bool {
+    matches!(self, Self::Node)
+  }
+
+  /// Condition name matched against package `exports` for this target.
+  pub fn condition_name(&self) -> &'static str {
+    match self {
+      Self::Web => "browser",
+      Self::Node => "node",
+    }
+  }
+}
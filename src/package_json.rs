@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use sugar_path::PathSugar;
+
+/// `sideEffects` field of a `package.json`.
+#[derive(Debug, Clone)]
+pub enum SideEffects {
+  Bool(bool),
+  /// Files with side effects, relative to the directory of the `package.json`.
+  Globs(Vec<glob::Pattern>),
+}
+
+#[derive(Debug)]
+pub struct PackageSideEffects {
+  pub dir: PathBuf,
+  pub side_effects: Option<SideEffects>,
+}
+
+impl PackageSideEffects {
+  fn parse(dir: PathBuf, content: &str) -> Self {
+    let json: serde_json::Value = serde_json::from_str(content).unwrap_or_default();
+    let side_effects = match json.get("sideEffects") {
+      Some(serde_json::Value::Bool(value)) => Some(SideEffects::Bool(*value)),
+      Some(serde_json::Value::String(pattern)) => Some(SideEffects::Globs(
+        glob_pattern(pattern).into_iter().collect(),
+      )),
+      Some(serde_json::Value::Array(patterns)) => Some(SideEffects::Globs(
+        patterns
+          .iter()
+          .filter_map(|pattern| pattern.as_str())
+          .filter_map(glob_pattern)
+          .collect(),
+      )),
+      _ => None,
+    };
+    Self { dir, side_effects }
+  }
+
+  /// Whether `path` has side effects, `None` if the package does not say.
+  pub fn has_side_effects(&self, path: &Path) -> Option<bool> {
+    match self.side_effects.as_ref()? {
+      SideEffects::Bool(value) => Some(*value),
+      SideEffects::Globs(patterns) => {
+        let relative = path.relative(&self.dir);
+        Some(patterns.iter().any(|pattern| pattern.matches_path(&relative)))
+      }
+    }
+  }
+}
+
+/// Like webpack, a pattern without `/` matches files in any directory.
+fn glob_pattern(pattern: &str) -> Option<glob::Pattern> {
+  let pattern = pattern.trim_start_matches("./");
+  let pattern = if pattern.contains('/') {
+    pattern.to_string()
+  } else {
+    format!("**/{}", pattern)
+  };
+  glob::Pattern::new(&pattern).ok()
+}
+
+/// Caches the nearest `package.json` of directories.
+#[derive(Debug, Default)]
+pub struct PackageJsonCache {
+  dir_to_package: DashMap<PathBuf, Option<Arc<PackageSideEffects>>>,
+}
+
+impl PackageJsonCache {
+  /// Reads `sideEffects` of the nearest `package.json` of `path`.
+  pub async fn side_effects(&self, path: &Path) -> Option<bool> {
+    let package = self.nearest_package(path.parent()?).await?;
+    package.has_side_effects(path)
+  }
+
+  async fn nearest_package(&self, dir: &Path) -> Option<Arc<PackageSideEffects>> {
+    let mut visited = vec![];
+    let mut current = Some(dir);
+    let mut found = None;
+    while let Some(dir) = current {
+      if let Some(cached) = self.dir_to_package.get(dir) {
+        found = cached.clone();
+        break;
+      }
+      visited.push(dir.to_path_buf());
+      if let Ok(content) = tokio::fs::read_to_string(dir.join("package.json")).await {
+        found = Some(Arc::new(PackageSideEffects::parse(dir.to_path_buf(), &content)));
+        break;
+      }
+      current = dir.parent();
+    }
+    for dir in visited {
+      self.dir_to_package.insert(dir, found.clone());
+    }
+    found
+  }
+}
@@ -1,29 +1,113 @@
 use std::fmt::Debug;
-use crate::{PluginContext, SourceType, JobContext, LoadArgs, ParseModuleArgs, BoxModule, RenderManifestArgs};
+use async_trait::async_trait;
+use crate::{PluginContext, SourceType, JobContext, LoadArgs, ParseModuleArgs, BoxModule, RenderManifestArgs, ResolveArgs, Content, TransformArgs, TransformOutput, ComplierOptions, Compilation, ProcessAssetsArgs, External};
+#[async_trait]
 pub trait Plugin: Debug + Send + Sync {
+  fn name(&self) -> &'static str {
+    std::any::type_name::<Self>()
+  }
+  fn enforce(&self) -> PluginEnforce {
+    PluginEnforce::Normal
+  }
+  /// Plugins with a higher priority run first within the same `enforce` group.
+  fn priority(&self) -> i32 {
+    0
+  }
   fn register_parse_module(&self, _ctx: PluginContext) -> Option<Vec<SourceType>> {
     None
   }
-  fn loader(&self, _ctx: PluginContext<& mut JobContext>, args: LoadArgs) -> Option<String> {
-    unreachable!()
+  /// Extensions, without the leading `.`, mapped to the module type of the files.
+  fn register_module_types(&self, _ctx: PluginContext) -> Vec<(&'static str, SourceType)> {
+    vec![]
+  }
+  /// Called before a new compilation is created.
+  async fn before_compile(&self, _ctx: PluginContext, _options: &ComplierOptions) -> anyhow::Result<()> {
+    Ok(())
+  }
+  /// Called right after a new compilation is created, before any entry is built.
+  async fn this_compilation(&self, _ctx: PluginContext, _compilation: &mut Compilation) -> anyhow::Result<()> {
+    Ok(())
+  }
+  /// Called before modules are built. Entries added here are built as well.
+  async fn make(&self, _ctx: PluginContext, _compilation: &mut Compilation) -> anyhow::Result<()> {
+    Ok(())
+  }
+  /// Called once the module graph is complete.
+  async fn finish_modules(&self, _ctx: PluginContext, _compilation: &mut Compilation) -> anyhow::Result<()> {
+    Ok(())
+  }
+  /// Called after chunks are split, before they are rendered.
+  async fn optimize_chunks(&self, _ctx: PluginContext, _compilation: &mut Compilation) -> anyhow::Result<()> {
+    Ok(())
+  }
+  /// Called once per `ProcessAssetsStage` after chunks are rendered.
+  async fn process_assets(&self, _ctx: PluginContext, _args: ProcessAssetsArgs<'_>) -> anyhow::Result<()> {
+    Ok(())
   }
-  fn parse_module(&self, _ctx: PluginContext<& mut JobContext>, args: ParseModuleArgs) -> BoxModule {
-    unreachable!()
+  /// Called after assets are written to the output directory.
+  async fn after_emit(&self, _ctx: PluginContext, _compilation: &Compilation) -> anyhow::Result<()> {
+    Ok(())
+  }
+  /// Called when the compilation is finished.
+  async fn done(&self, _ctx: PluginContext, _compilation: &Compilation) -> anyhow::Result<()> {
+    Ok(())
+  }
+  /// Returning `None` falls through to the next plugin and finally to the built-in resolver.
+  async fn resolve(&self, _ctx: PluginContext<&mut JobContext>, _args: &ResolveArgs<'_>) -> anyhow::Result<Option<ResolvedUri>> {
+    Ok(None)
+  }
+  /// Returning `None` falls through to the next plugin and finally to reading from disk.
+  async fn loader(&self, _ctx: PluginContext<&mut JobContext>, _args: &LoadArgs<'_>) -> anyhow::Result<Option<Content>> {
+    Ok(None)
+  }
+  /// Runs after loading and before parsing. Plugins are chained in order, each one
+  /// receiving the code returned by the previous one.
+  async fn transform(&self, _ctx: PluginContext<&mut JobContext>, args: TransformArgs<'_>) -> anyhow::Result<TransformOutput> {
+    Ok(TransformOutput {
+      code: args.source,
+      ..Default::default()
+    })
+  }
+  async fn parse_module(&self, _ctx: PluginContext<&mut JobContext>, _args: ParseModuleArgs<'_>) -> anyhow::Result<BoxModule> {
+    anyhow::bail!("{} registers a parser but does not implement parse_module", self.name())
   }
 
-  fn render_manifest(&self, _ctx: PluginContext, _args: RenderManifestArgs) -> Vec<Asset> {
-    vec![]
+  /// Js chunks should render their modules with `Chunk::render_js_modules`, which
+  /// takes care of concatenated scopes and non-js modules, place
+  /// `Chunk::hoisted_imports` at their top level and await the exports of modules
+  /// for which `Module::is_async` is true.
+  fn render_manifest(&self, _ctx: PluginContext, _args: RenderManifestArgs) -> anyhow::Result<Vec<Asset>> {
+    Ok(vec![])
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum PluginEnforce {
+  Pre,
+  #[default]
+  Normal,
+  Post,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolvedUri {
+  /// A file path or a virtual path which is loaded and parsed as usual. The query
+  /// and fragment of the request are added to it to form the uri of the module.
+  Path(String),
+  /// The request is left to the runtime and not bundled.
+  External(External),
+  /// The request is replaced by an empty module.
+  Ignored,
+}
+
+#[derive(Debug, Clone)]
 pub enum AssetFilename {
   Static(String),
   Templace(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Asset {
-  pub rendered: String,
+  pub rendered: Content,
   pub filename: AssetFilename,
 }
@@ -1,9 +1,13 @@
-use crate::{ResolveKind, Compilation};
+use crate::{ResolveKind, Compilation, SourceType, ResourceData};
 
 #[derive(Debug)]
 pub struct ParseModuleArgs<'a> {
   pub uri: &'a str,
-  pub source: String,
+  /// Parts of `uri`, use it instead of parsing `uri`.
+  pub resource: ResourceData<'a>,
+  pub source: Content,
+  /// Source map produced by `Plugin::transform`, if any.
+  pub map: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -12,12 +16,119 @@ pub struct RenderManifestArgs<'me> {
   pub compilation: &'me Compilation,
 }
 
+#[derive(Debug)]
+pub struct ProcessAssetsArgs<'me> {
+  pub stage: ProcessAssetsStage,
+  pub compilation: &'me mut Compilation,
+}
+
+/// Stages of `Plugin::process_assets`, run in declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ProcessAssetsStage {
+  /// Add additional assets.
+  Additional,
+  /// Basic preprocessing of assets.
+  PreProcess,
+  /// Derive new assets from existing ones.
+  Derived,
+  /// Add additional sections to existing assets, like a banner.
+  Additions,
+  /// Optimize existing assets.
+  Optimize,
+  /// Optimize the size of existing assets, e.g. by minimizing.
+  OptimizeSize,
+  /// Add development tooling, e.g. extracting source maps.
+  DevTooling,
+  /// Summarize the list of existing assets.
+  Summarize,
+  /// Create reports from the final assets.
+  Report,
+}
+
+impl ProcessAssetsStage {
+  pub fn all() -> [ProcessAssetsStage; 9] {
+    [
+      Self::Additional,
+      Self::PreProcess,
+      Self::Derived,
+      Self::Additions,
+      Self::Optimize,
+      Self::OptimizeSize,
+      Self::DevTooling,
+      Self::Summarize,
+      Self::Report,
+    ]
+  }
+}
+
 pub struct ResolveArgs<'a> {
   pub importer: Option<&'a str>,
+  /// Path of the importer without its query and fragment.
+  pub importer_path: Option<&'a str>,
   pub specifier: &'a str,
   pub kind: ResolveKind,
 }
 
 pub struct LoadArgs<'a> {
   pub uri: &'a str,
+  /// Parts of `uri`, use it instead of parsing `uri`.
+  pub resource: ResourceData<'a>,
+}
+
+#[derive(Debug)]
+pub struct TransformArgs<'a> {
+  pub uri: &'a str,
+  pub source: Content,
+  pub source_type: SourceType,
+}
+
+#[derive(Debug, Default)]
+pub struct TransformOutput {
+  pub code: Content,
+  pub map: Option<String>,
+  /// Extra files the output depends on, e.g. included partials.
+  pub file_dependencies: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+  String(String),
+  Buffer(Vec<u8>),
+}
+
+impl Content {
+  pub fn as_bytes(&self) -> &[u8] {
+    match self {
+      Content::String(s) => s.as_bytes(),
+      Content::Buffer(b) => b,
+    }
+  }
+
+  pub fn try_into_string(self) -> anyhow::Result<String> {
+    match self {
+      Content::String(s) => Ok(s),
+      Content::Buffer(b) => Ok(String::from_utf8(b)?),
+    }
+  }
+}
+
+impl Default for Content {
+  fn default() -> Self {
+    Self::String(String::new())
+  }
+}
+
+impl From<String> for Content {
+  fn from(s: String) -> Self {
+    Self::String(s)
+  }
+}
+
+impl From<Vec<u8>> for Content {
+  fn from(buf: Vec<u8>) -> Self {
+    match String::from_utf8(buf) {
+      Ok(s) => Self::String(s),
+      Err(err) => Self::Buffer(err.into_bytes()),
+    }
+  }
 }
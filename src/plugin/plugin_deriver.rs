@@ -1,5 +1,8 @@
-use crate::{ComplierOptions, SourceType, PluginContext, ParseModuleArgs, JobContext, BoxModule};
+use crate::{ComplierOptions, SourceType, PluginContext, ParseModuleArgs, JobContext, BoxModule, ResolveKind, ResolveArgs, ResolvedUri, resolve, LoadArgs, Content, load, TransformArgs, TransformOutput, Compilation, ProcessAssetsArgs, ProcessAssetsStage, Diagnostic, RenderManifestArgs, Asset, JsonModule, AssetModule, CssModule, ResourceData, resolve_source_type_by_query, resolve_source_type_by_resource, ExternalArgs, PackageJsonCache};
 
+use anyhow::Context;
+use std::cmp::Reverse;
+use std::path::Path;
 use std::sync::Arc;
 use crate::{Plugin};
 use std::collections::HashMap;
@@ -7,57 +10,317 @@ use nodejs_resolver::Resolver;
 pub struct PluginDriver {
   pub(crate) options: Arc<ComplierOptions>,
   pub plugins: Vec<Box<dyn Plugin>>,
-  pub resolver: Arc<Resolver>,
-  pub module_parser: HashMap<SourceType, usize>
+  pub resolvers: HashMap<ResolveKind, Arc<Resolver>>,
+  pub module_parser: HashMap<SourceType, usize>,
+  /// Extensions registered by plugins through `Plugin::register_module_types`.
+  pub module_types: HashMap<String, SourceType>,
+  /// Diagnostics found while setting up plugins.
+  pub diagnostics: Vec<Diagnostic>,
+  pub package_json_cache: PackageJsonCache,
 }
 
 impl PluginDriver {
   pub fn new(
     options: Arc<ComplierOptions>,
-    plugins: Vec<Box<dyn Plugin>>,
-    resolver: Arc<Resolver>,
+    mut plugins: Vec<Box<dyn Plugin>>,
+    resolvers: HashMap<ResolveKind, Arc<Resolver>>,
   ) -> Self {
-    let module = plugins
-      .iter()
-      .enumerate()
-      .filter_map(|(index, plugin)| {
-        let registered = plugin.register_parse_module(PluginContext::new())?;
-        Some(
-          registered
-            .into_iter()
-            .map(|source_type| (source_type, index))
-            .collect::<Vec<_>>(),
-        )
-      })
-      .flatten()
-      .collect();
+    // Stable sort, plugins with the same order keep the order they are passed in.
+    plugins.sort_by_key(|plugin| (plugin.enforce(), Reverse(plugin.priority())));
+
+    let mut diagnostics = vec![];
+    let mut module_parser: HashMap<SourceType, usize> = HashMap::new();
+    for (index, plugin) in plugins.iter().enumerate() {
+      let registered = plugin
+        .register_parse_module(PluginContext::new())
+        .unwrap_or_default();
+      for source_type in registered {
+        match module_parser.get(&source_type) {
+          Some(claimed) => diagnostics.push(Diagnostic::warn(
+            "Conflicting parser",
+            format!(
+              "{} and {} both register a parser for {:?}, using {}",
+              plugins[*claimed].name(),
+              plugin.name(),
+              source_type,
+              plugins[*claimed].name(),
+            ),
+          )),
+          None => {
+            module_parser.insert(source_type, index);
+          }
+        }
+      }
+    }
+    let mut module_types: HashMap<String, SourceType> = HashMap::new();
+    for plugin in &plugins {
+      for (ext, source_type) in plugin.register_module_types(PluginContext::new()) {
+        match module_types.get(ext) {
+          Some(registered) if *registered != source_type => diagnostics.push(Diagnostic::warn(
+            "Conflicting module type",
+            format!(
+              "{} registers .{} as {:?} but it is already registered as {:?}",
+              plugin.name(),
+              ext,
+              source_type,
+              registered,
+            ),
+          )),
+          Some(_) => {}
+          None => {
+            module_types.insert(ext.to_string(), source_type);
+          }
+        }
+      }
+    }
     Self {
       options,
       plugins,
-      resolver,
-      module_parser: module,
+      resolvers,
+      module_parser,
+      module_types,
+      diagnostics,
+      package_json_cache: Default::default(),
+    }
+  }
+
+  pub async fn before_compile(&self) -> anyhow::Result<()> {
+    for plugin in &self.plugins {
+      plugin
+        .before_compile(PluginContext::new(), &self.options)
+        .await
+        .with_context(|| hook_failed(plugin.as_ref(), "before_compile"))?;
+    }
+    Ok(())
+  }
+
+  pub async fn this_compilation(&self, compilation: &mut Compilation) -> anyhow::Result<()> {
+    for plugin in &self.plugins {
+      plugin
+        .this_compilation(PluginContext::new(), compilation)
+        .await
+        .with_context(|| hook_failed(plugin.as_ref(), "this_compilation"))?;
+    }
+    Ok(())
+  }
+
+  pub async fn make(&self, compilation: &mut Compilation) -> anyhow::Result<()> {
+    for plugin in &self.plugins {
+      plugin
+        .make(PluginContext::new(), compilation)
+        .await
+        .with_context(|| hook_failed(plugin.as_ref(), "make"))?;
+    }
+    Ok(())
+  }
+
+  pub async fn finish_modules(&self, compilation: &mut Compilation) -> anyhow::Result<()> {
+    for plugin in &self.plugins {
+      plugin
+        .finish_modules(PluginContext::new(), compilation)
+        .await
+        .with_context(|| hook_failed(plugin.as_ref(), "finish_modules"))?;
+    }
+    Ok(())
+  }
+
+  pub async fn optimize_chunks(&self, compilation: &mut Compilation) -> anyhow::Result<()> {
+    for plugin in &self.plugins {
+      plugin
+        .optimize_chunks(PluginContext::new(), compilation)
+        .await
+        .with_context(|| hook_failed(plugin.as_ref(), "optimize_chunks"))?;
+    }
+    Ok(())
+  }
+
+  pub fn render_manifest(&self, args: RenderManifestArgs) -> anyhow::Result<Vec<Asset>> {
+    let mut assets = vec![];
+    for plugin in &self.plugins {
+      let rendered = plugin
+        .render_manifest(PluginContext::new(), args.clone())
+        .with_context(|| {
+          format!("{} on chunk {}", hook_failed(plugin.as_ref(), "render_manifest"), args.chunk_id)
+        })?;
+      assets.extend(rendered);
+    }
+    Ok(assets)
+  }
+
+  pub async fn process_assets(&self, compilation: &mut Compilation) -> anyhow::Result<()> {
+    for stage in ProcessAssetsStage::all() {
+      for plugin in &self.plugins {
+        plugin
+          .process_assets(
+            PluginContext::new(),
+            ProcessAssetsArgs {
+              stage,
+              compilation: &mut *compilation,
+            },
+          )
+          .await
+          .with_context(|| {
+            format!("{} at stage {:?}", hook_failed(plugin.as_ref(), "process_assets"), stage)
+          })?;
+      }
+    }
+    Ok(())
+  }
+
+  pub async fn after_emit(&self, compilation: &Compilation) -> anyhow::Result<()> {
+    for plugin in &self.plugins {
+      plugin
+        .after_emit(PluginContext::new(), compilation)
+        .await
+        .with_context(|| hook_failed(plugin.as_ref(), "after_emit"))?;
     }
+    Ok(())
   }
 
-  pub fn parse_module(
+  pub async fn done(&self, compilation: &Compilation) -> anyhow::Result<()> {
+    for plugin in &self.plugins {
+      plugin
+        .done(PluginContext::new(), compilation)
+        .await
+        .with_context(|| hook_failed(plugin.as_ref(), "done"))?;
+    }
+    Ok(())
+  }
+
+  /// Resolves the module type of `resource` by its query, then the extensions
+  /// registered by plugins, then the built-in extensions.
+  pub fn resolve_source_type(&self, resource: &ResourceData) -> Option<SourceType> {
+    if let Some(source_type) = resolve_source_type_by_query(resource.query_value()) {
+      return Some(source_type);
+    }
+    let ext = Path::new(resource.path).extension()?.to_str()?;
+    self
+      .module_types
+      .get(ext)
+      .copied()
+      .or_else(|| resolve_source_type_by_resource(resource))
+  }
+
+  pub fn resolver(&self, kind: ResolveKind) -> &Resolver {
+    self
+      .resolvers
+      .get(&kind)
+      .unwrap_or_else(|| panic!("no resolver found for kind: {:?}", kind))
+  }
+
+  pub async fn resolve(
+    &self,
+    args: ResolveArgs<'_>,
+    job_ctx: &mut JobContext,
+  ) -> anyhow::Result<ResolvedUri> {
+    if args.importer.is_some() {
+      let external_args = ExternalArgs {
+        importer: args.importer,
+        specifier: args.specifier,
+        kind: args.kind,
+      };
+      let external = self
+        .options
+        .externals
+        .iter()
+        .find_map(|item| item.external(&external_args));
+      if let Some(external) = external {
+        return Ok(ResolvedUri::External(external));
+      }
+    }
+    for plugin in &self.plugins {
+      let resolved = plugin
+        .resolve(PluginContext::with_context(&mut *job_ctx), &args)
+        .await
+        .with_context(|| {
+          format!(
+            "{} for {:?} imported by {:?}",
+            hook_failed(plugin.as_ref(), "resolve"),
+            args.specifier,
+            args.importer.unwrap_or("<entry>"),
+          )
+        })?;
+      if let Some(resolved) = resolved {
+        return Ok(resolved);
+      }
+    }
+    resolve(args, self)
+  }
+
+  pub async fn load(&self, args: LoadArgs<'_>, job_ctx: &mut JobContext) -> anyhow::Result<Content> {
+    for plugin in &self.plugins {
+      let content = plugin
+        .loader(PluginContext::with_context(&mut *job_ctx), &args)
+        .await
+        .with_context(|| format!("{} on {}", hook_failed(plugin.as_ref(), "loader"), args.uri))?;
+      if let Some(content) = content {
+        return Ok(content);
+      }
+    }
+    load(args).await
+  }
+
+  /// Source maps are not composed. A plugin changing the code without returning a
+  /// map drops the map of the previous plugins, which no longer matches the code.
+  pub async fn transform(
+    &self,
+    args: TransformArgs<'_>,
+    job_ctx: &mut JobContext,
+  ) -> anyhow::Result<TransformOutput> {
+    let TransformArgs { uri, source, source_type } = args;
+    let mut output = TransformOutput {
+      code: source,
+      ..Default::default()
+    };
+    for plugin in &self.plugins {
+      let previous_code = output.code.clone();
+      let transformed = plugin
+        .transform(
+          PluginContext::with_context(&mut *job_ctx),
+          TransformArgs {
+            uri,
+            source: output.code,
+            source_type,
+          },
+        )
+        .await
+        .with_context(|| format!("{} on {}", hook_failed(plugin.as_ref(), "transform"), uri))?;
+      if transformed.map.is_some() || transformed.code != previous_code {
+        output.map = transformed.map;
+      }
+      output.code = transformed.code;
+      output.file_dependencies.extend(transformed.file_dependencies);
+    }
+    Ok(output)
+  }
+
+  pub async fn parse_module(
     &self,
-    args: ParseModuleArgs,
+    args: ParseModuleArgs<'_>,
     job_ctx: &mut JobContext,
   ) -> anyhow::Result<BoxModule> {
-    let parse_index = self
-      .module_parser
-      .get(
-        job_ctx
-          .source_type
-          .as_ref()
-          .ok_or_else(|| anyhow::format_err!("source type not found"))?,
-      )
-      .unwrap_or_else(|| {
-        panic!("no parser found for source type: {:?}", &job_ctx.source_type)
-      });
-    
-      let module =
-        self.plugins[*parse_index].parse_module(PluginContext::with_context(job_ctx), args);
-      Ok(module)
-  }
-}
\ No newline at end of file
+    let source_type = job_ctx
+      .source_type
+      .ok_or_else(|| anyhow::format_err!("source type not found for {}", args.uri))?;
+    let parse_index = match self.module_parser.get(&source_type) {
+      Some(index) => index,
+      None if source_type == SourceType::Json => return Ok(Box::new(JsonModule::parse(args)?)),
+      None if source_type == SourceType::Css => return Ok(Box::new(CssModule::parse(args, &self.options)?)),
+      None if source_type.is_asset() => {
+        return Ok(Box::new(AssetModule::parse(args, source_type, &self.options)?))
+      }
+      None => anyhow::bail!("no parser found for source type {:?} of {}", source_type, args.uri),
+    };
+
+    let plugin = &self.plugins[*parse_index];
+    let uri = args.uri;
+    plugin
+      .parse_module(PluginContext::with_context(job_ctx), args)
+      .await
+      .with_context(|| format!("{} on {}", hook_failed(plugin.as_ref(), "parse_module"), uri))
+  }
+}
+
+fn hook_failed(plugin: &dyn Plugin, hook: &str) -> String {
+  format!("plugin {} failed in {}", plugin.name(), hook)
+}
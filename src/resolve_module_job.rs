@@ -5,14 +5,17 @@ use std::{
   }
 };
 use dashmap::DashSet;
-use crate::{SourceType, PluginDriver, Msg, ResolveArgs, LoadArgs, ParseModuleArgs, ModuleGraphModule};
+use crate::{SourceType, PluginDriver, Msg, ResolveArgs, LoadArgs, ParseModuleArgs, ModuleGraphModule, ResolvedUri, Content, run_loaders, TransformArgs, Diagnostic, External, ExternalModule, EmptyModule, BoxModule};
+use anyhow::Context;
 use tokio::sync::mpsc::UnboundedSender;
 use std::path::Path;
+use sugar_path::PathSugar;
 use tracing::trace;
-use nodejs_resolver::ResolveResult;
+use nodejs_resolver::{ResolveResult, Resolver, ResolverOptions};
+use crate::ComplierOptions;
 
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Dependency {
   /// Uri of importer module
   pub importer: Option<String>,
@@ -21,12 +24,93 @@ pub struct Dependency {
   pub kind: ResolveKind,
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum ResolveKind {
   Import,
   Require,
   DynamicImport,
   AtImport,
+  /// `url()` in css.
+  Url,
+}
+
+impl ResolveKind {
+  pub fn all() -> [ResolveKind; 5] {
+    [
+      ResolveKind::Import,
+      ResolveKind::Require,
+      ResolveKind::DynamicImport,
+      ResolveKind::AtImport,
+      ResolveKind::Url,
+    ]
+  }
+}
+
+/// A request split into path, query and fragment, e.g. `./logo.svg?raw#top`.
+///
+/// `query` and `fragment` keep their leading `?` and `#`, so concatenating the parts
+/// gives back the request. A leading `#` belongs to the path, as in `#internal` of
+/// package `imports`.
+///
+/// Only parse requests, a resolved path may contain `?` and `#` itself, see `Resource`.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub struct ResourceData<'a> {
+  pub path: &'a str,
+  pub query: &'a str,
+  pub fragment: &'a str,
+}
+
+impl<'a> ResourceData<'a> {
+  pub fn parse(request: &'a str) -> Self {
+    let path_start = usize::from(request.starts_with('#'));
+    let (rest, fragment) = match request[path_start..].find('#') {
+      Some(index) => request.split_at(path_start + index),
+      None => (request, ""),
+    };
+    let (path, query) = match rest.find('?') {
+      Some(index) => rest.split_at(index),
+      None => (rest, ""),
+    };
+    Self {
+      path,
+      query,
+      fragment,
+    }
+  }
+
+  /// Query without the leading `?`.
+  pub fn query_value(&self) -> &'a str {
+    self.query.trim_start_matches('?')
+  }
+
+  /// Fragment without the leading `#`.
+  pub fn fragment_value(&self) -> &'a str {
+    self.fragment.trim_start_matches('#')
+  }
+}
+
+/// A resolved path with the query and fragment of the request, kept apart so that a
+/// path containing `?` or `#` is never split.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Default)]
+pub struct Resource {
+  pub path: String,
+  pub query: String,
+  pub fragment: String,
+}
+
+impl Resource {
+  /// Uri of the module, the parts concatenated.
+  pub fn uri(&self) -> String {
+    format!("{}{}{}", self.path, self.query, self.fragment)
+  }
+
+  pub fn as_data(&self) -> ResourceData<'_> {
+    ResourceData {
+      path: &self.path,
+      query: &self.query,
+      fragment: &self.fragment,
+    }
+  }
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
@@ -34,8 +118,18 @@ pub struct ModuleDependency {
   pub specifier: String,
   pub kind: ResolveKind,
 }
+
+impl From<&Dependency> for ModuleDependency {
+  fn from(dep: &Dependency) -> Self {
+    Self {
+      specifier: dep.specifier.clone(),
+      kind: dep.kind,
+    }
+  }
+}
 #[derive(Debug, Clone)]
 pub struct JobContext {
+  /// Path of the importer module without its query and fragment.
   pub importer: Option<String>,
   pub(crate) active_task_acount: Arc<AtomicUsize>,
   pub(crate) visited_module_uri: Arc<DashSet<String>>,
@@ -53,6 +147,8 @@ pub struct ResolveModuleJob {
   pub dependency: Dependency,
   pub tx: UnboundedSender<Msg>,
   pub plugin_driver: Arc<PluginDriver>,
+  /// Set once the dependency is resolved, failures after that are reported on it.
+  resolved_uri: Option<String>,
 }
 
 impl ResolveModuleJob {
@@ -69,70 +165,196 @@ impl ResolveModuleJob {
       dependency,
       tx,
       plugin_driver,
+      resolved_uri: None,
     }
   }
 
   pub async fn run(mut self) {
-    let uri = resolve(ResolveArgs { 
-      importer: self.dependency.importer.as_deref(), 
-      specifier: self.dependency.specifier.as_str(), 
-      kind: self.dependency.kind 
-    },
-    &self.plugin_driver);
-    trace!("resolved uri: {:?}", uri);
-    let source_type = resolve_source_type_by_uri(&uri);
-    self.context.set_source_type(source_type.unwrap()); 
+    if let Err(err) = self.build().await {
+      let message = match &self.dependency.importer {
+        Some(importer) => format!("{:#}\nimported by {}", err, importer),
+        None => format!("{:#}", err),
+      };
+      let mut diagnostic = Diagnostic::error("Module build failed", message);
+      if let Some(uri) = self.resolved_uri.take() {
+        diagnostic = diagnostic.with_uri(uri);
+      }
+      self.tx.send(Msg::TaskFailed(diagnostic)).unwrap();
+    }
+  }
+
+  /// Every path that returns `Ok` reports the end of the task through `tx`.
+  async fn build(&mut self) -> anyhow::Result<()> {
+    let importer_path = self.context.importer.clone();
+    let resolved = self
+      .plugin_driver
+      .resolve(
+        ResolveArgs {
+          importer: self.dependency.importer.as_deref(),
+          importer_path: importer_path.as_deref(),
+          specifier: self.dependency.specifier.as_str(),
+          kind: self.dependency.kind,
+        },
+        &mut self.context,
+      )
+      .await;
+    let resolved = match resolved {
+      Ok(resolved) => resolved,
+      Err(err) => {
+        let mut diagnostic = Diagnostic::error("Module not found", format!("{:#}", err));
+        if let Some(importer) = &self.dependency.importer {
+          diagnostic = diagnostic.with_uri(importer.clone());
+        }
+        self.tx.send(Msg::TaskFailed(diagnostic)).unwrap();
+        return Ok(());
+      }
+    };
+    trace!("resolved uri: {:?}", resolved);
+    let path = match resolved {
+      ResolvedUri::Path(path) => path,
+      ResolvedUri::External(external) => {
+        self.add_external_module(external);
+        return Ok(());
+      }
+      ResolvedUri::Ignored => {
+        let uri = EmptyModule::uri(&self.dependency.specifier);
+        self.add_builtin_module(uri, Box::new(EmptyModule));
+        return Ok(());
+      }
+    };
+    let request = ResourceData::parse(&self.dependency.specifier);
+    let resource = Resource {
+      path,
+      query: request.query.to_string(),
+      fragment: request.fragment.to_string(),
+    };
+    let uri = resource.uri();
+    self.resolved_uri = Some(uri.clone());
+    let rules = self
+      .plugin_driver
+      .options
+      .module
+      .rules
+      .iter()
+      .filter(|rule| rule.matches(&resource.as_data()))
+      .collect::<Vec<_>>();
+    let source_type = match rules
+      .iter()
+      .rev()
+      .find_map(|rule| rule.module_type)
+      .or_else(|| self.plugin_driver.resolve_source_type(&resource.as_data()))
+    {
+      Some(source_type) => source_type,
+      None => {
+        let diagnostic = Diagnostic::error(
+          "Unknown module type",
+          format!(
+            "can not determine the module type of {}, add a module rule with `module_type` or register the extension from a plugin",
+            uri
+          ),
+        )
+        .with_uri(uri);
+        self.tx.send(Msg::TaskFailed(diagnostic)).unwrap();
+        return Ok(());
+      }
+    };
+    self.context.set_source_type(source_type);
     self
       .tx
-      .send(
-        Msg::DependencyReference(self.dependency.clone(), 
-        uri.clone()
-    ))
-    .unwrap();
+      .send(Msg::DependencyReference(self.dependency.clone(), uri.clone()))
+      .unwrap();
     if self.context.visited_module_uri.contains(&uri) {
       self.tx.send(Msg::TaskErrorEncountered(())).unwrap();
-    } else {
-      self.context.visited_module_uri.insert(uri.clone());
-      let source = load(LoadArgs { uri: uri.as_str() }).await;
-      let mut module = self
-        .plugin_driver
-        .parse_module(
-          ParseModuleArgs {
-            uri: uri.as_str(),
-            source: source,
-          },
-          &mut self.context,
-        )
-        .unwrap();
-
-      let deps = module
-        .dependencies()
-        .iter()
-        .map(|dep| Dependency {
-          importer: Some(uri.clone()),
-          specifier: dep.specifier.clone(),
-          kind: dep.kind,
-        })
-        .collect::<Vec<_>>();
-
-      deps.iter().for_each(|dep| {
-        self.fork(dep.clone());
-      });
-
-      self
-        .tx
-        .send(
-          Msg::TaskFinished(Box::new(ModuleGraphModule::new(
-            module,
-            uri,
-            source_type.unwrap(),
-            deps,
-          ))),
-        )
-        .unwrap();
+      return Ok(());
+    }
+    self.context.visited_module_uri.insert(uri.clone());
+    let source = self
+      .plugin_driver
+      .load(
+        LoadArgs {
+          uri: uri.as_str(),
+          resource: resource.as_data(),
+        },
+        &mut self.context,
+      )
+      .await?;
+    let source = run_loaders(&rules, &uri, source)?;
+    let transformed = self
+      .plugin_driver
+      .transform(
+        TransformArgs {
+          uri: uri.as_str(),
+          source,
+          source_type,
+        },
+        &mut self.context,
+      )
+      .await?;
+    let mut module = self
+      .plugin_driver
+      .parse_module(
+        ParseModuleArgs {
+          uri: uri.as_str(),
+          resource: resource.as_data(),
+          source: transformed.code,
+          map: transformed.map,
+        },
+        &mut self.context,
+      )
+      .await?;
+
+    let deps = module
+      .dependencies()
+      .iter()
+      .map(|dep| Dependency {
+        importer: Some(uri.clone()),
+        specifier: dep.specifier.clone(),
+        kind: dep.kind,
+      })
+      .collect::<Vec<_>>();
+
+    self.context.importer = Some(resource.path.clone());
+    deps.iter().for_each(|dep| {
+      self.fork(dep.clone());
+    });
+
+    let side_effects = self
+      .plugin_driver
+      .package_json_cache
+      .side_effects(Path::new(&resource.path))
+      .await;
+    let mut module = ModuleGraphModule::new(module, uri, source_type, deps);
+    module.resource = resource;
+    module.file_dependencies = transformed.file_dependencies;
+    module.side_effects = side_effects;
+    self
+      .tx
+      .send(Msg::TaskFinished(Box::new(module)))
+      .unwrap();
+    Ok(())
+  }
 
+  fn add_external_module(&self, external: External) {
+    let uri = ExternalModule::uri(&external);
+    let module = ExternalModule::new(self.dependency.specifier.clone(), external);
+    self.add_builtin_module(uri, Box::new(module));
+  }
+
+  /// Adds a module which is not loaded from a file and has no dependencies.
+  fn add_builtin_module(&self, uri: String, module: BoxModule) {
+    self
+      .tx
+      .send(Msg::DependencyReference(self.dependency.clone(), uri.clone()))
+      .unwrap();
+    if !self.context.visited_module_uri.insert(uri.clone()) {
+      self.tx.send(Msg::TaskErrorEncountered(())).unwrap();
+      return;
     }
-    println!("running")
+    let module = ModuleGraphModule::new(module, uri, SourceType::Js, vec![]);
+    self
+      .tx
+      .send(Msg::TaskFinished(Box::new(module)))
+      .unwrap();
   }
 
   pub fn fork(&self, dep: Dependency) {
@@ -144,50 +366,116 @@ impl ResolveModuleJob {
   }
 }
 
-pub fn resolve_source_type_by_uri<T: AsRef<Path>>(uri: T) -> Option<SourceType> {
-  let path = uri.as_ref();
-  let ext = path.extension()?.to_str()?;
+/// `?raw`, `?url` and `?inline` load any file as the matching asset type, otherwise
+/// the source type is inferred from the extension.
+pub fn resolve_source_type_by_uri<T: AsRef<str>>(uri: T) -> Option<SourceType> {
+  resolve_source_type_by_resource(&ResourceData::parse(uri.as_ref()))
+}
+
+pub fn resolve_source_type_by_resource(resource: &ResourceData) -> Option<SourceType> {
+  if let Some(source_type) = resolve_source_type_by_query(resource.query_value()) {
+    return Some(source_type);
+  }
+  let ext = Path::new(resource.path).extension()?.to_str()?;
   let source_type: Option<SourceType> = ext.try_into().ok();
   source_type
 }
 
-pub async fn load(args: LoadArgs<'_>) -> String {
-  tokio::fs::read_to_string(args.uri)
+pub fn resolve_source_type_by_query(query: &str) -> Option<SourceType> {
+  match query {
+    "raw" => Some(SourceType::AssetSource),
+    "url" => Some(SourceType::AssetResource),
+    "inline" => Some(SourceType::AssetInline),
+    _ => None,
+  }
+}
+
+pub async fn load(args: LoadArgs<'_>) -> anyhow::Result<Content> {
+  let content = tokio::fs::read(args.resource.path)
     .await
-    .unwrap_or_else(|_| panic!("fail to load uri: {:?}", args.uri))
+    .with_context(|| format!("fail to load uri: {:?}", args.uri))?;
+  Ok(content.into())
 }
 
-pub fn resolve(args: ResolveArgs, plugin_driver: &PluginDriver) -> String {
+/// Resolves the path of the request, relative to the directory of the importer.
+pub fn resolve(args: ResolveArgs, plugin_driver: &PluginDriver) -> anyhow::Result<ResolvedUri> {
+  let request = ResourceData::parse(args.specifier);
   if let Some(importer) = args.importer {
-    let base_dir = Path::new(importer).parent().unwrap();
+    let base_dir = Path::new(args.importer_path.unwrap_or(importer))
+      .parent()
+      .ok_or_else(|| anyhow::format_err!("importer {:?} has no parent directory", importer))?;
     tracing::trace!(
       "resolved importer:{:?},specifier:{:?}",
       importer,
       args.specifier
     );
-    match plugin_driver
-      .resolver
-      .resolve(base_dir, args.specifier)
-      .unwrap_or_else(|_| {
-        panic!(
-          "fail to resolved importer:{:?},specifier:{:?}",
-          importer, args.specifier
+    let resolved = plugin_driver
+      .resolver(args.kind)
+      .resolve(base_dir, request.path)
+      .map_err(|err| {
+        anyhow::format_err!(
+          "can't resolve {:?} imported by {}: {}",
+          args.specifier,
+          importer,
+          err
         )
-    }) {
-      ResolveResult::Path(path) => path.to_string_lossy().to_string(),
-      _ => {
+      })?;
+    match resolved {
+      ResolveResult::Path(path) => Ok(ResolvedUri::Path(path.to_string_lossy().to_string())),
+      ResolveResult::Ignored => {
         tracing::trace!(
-          "resolved importer:{:?},specifier:{:?} to None",
+          "resolved importer:{:?},specifier:{:?} to ignored",
           importer,
           args.specifier
         );
-        args.specifier.to_string()
+        Ok(ResolvedUri::Ignored)
       }
     }
   } else {
-    Path::new(plugin_driver.options.root.as_str())
-      .join(args.specifier)
-      .to_string_lossy()
-      .to_string()
+    Ok(ResolvedUri::Path(
+      Path::new(plugin_driver.options.root.as_str())
+        .join(request.path)
+        .resolve()
+        .to_string_lossy()
+        .to_string(),
+    ))
   }
-}
\ No newline at end of file
+}
+
+/// Builds the resolver used for dependencies of `kind`.
+///
+/// `import` and `require` pick different conditions from package `exports`, and
+/// `@import` follows css conventions (`style` field, `.css` extension).
+pub fn create_resolver(options: &ComplierOptions, kind: ResolveKind) -> Resolver {
+  let target = options.target;
+  let mut condition_names = vec![target.condition_name()];
+  let (extensions, main_fields) = match kind {
+    ResolveKind::Import | ResolveKind::DynamicImport => {
+      condition_names.push("import");
+      (vec![".tsx", ".jsx", ".ts", ".js", ".json"], vec!["module", "main"])
+    }
+    ResolveKind::Require => {
+      condition_names.push("require");
+      (vec![".tsx", ".jsx", ".ts", ".js", ".json"], vec!["main"])
+    }
+    ResolveKind::AtImport => {
+      condition_names.push("style");
+      (vec![".css"], vec!["style", "main"])
+    }
+    ResolveKind::Url => (vec![], vec![]),
+  };
+  condition_names.push("default");
+  let alias_fields = if target.is_web() {
+    vec![String::from("browser")]
+  } else {
+    vec![]
+  };
+  Resolver::new(ResolverOptions {
+    extensions: extensions.into_iter().map(|s| s.to_string()).collect(),
+    main_fields: main_fields.into_iter().map(|s| s.to_string()).collect(),
+    condition_names: condition_names.into_iter().map(|s| s.to_string()).collect(),
+    alias_fields,
+    prefer_relative: matches!(kind, ResolveKind::AtImport | ResolveKind::Url),
+    ..Default::default()
+  })
+}
@@ -0,0 +1,19 @@
+#[derive(Debug, Default)]
+pub struct Stats {
+  /// Modules left out of every chunk.
+  pub dropped_modules: Vec<DroppedModule>,
+  /// Modules kept in their own wrapper by `concatenate_modules`.
+  pub concatenation_bailouts: Vec<ConcatenationBailout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedModule {
+  pub uri: String,
+  pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcatenationBailout {
+  pub uri: String,
+  pub reason: String,
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::{
+  BoxModule, Compilation, ConcatenationScope, Dependency, EntryItem, ImportedModule,
+  ImportedNames, JobContext, Module, ModuleDependency, ModuleGraphModule, ParseModuleArgs, Plugin,
+  PluginContext, ResolveKind, SourceType, UsedExports,
+};
+
+/// Module with hand-written behavior, for building graphs in tests.
+#[derive(Debug, Default)]
+pub struct TestModule {
+  pub code: String,
+  pub dependencies: Vec<ModuleDependency>,
+  pub esm: bool,
+  /// Top-level bindings, each one exported under its own name.
+  pub bindings: Vec<String>,
+  /// Names imported through a specifier, everything when missing.
+  pub imports: HashMap<String, Vec<String>>,
+  /// Names re-exported from a specifier, `None` for `export *`.
+  pub reexports: HashMap<String, Option<Vec<String>>>,
+  pub side_effect_free: bool,
+}
+
+impl TestModule {
+  pub fn new(code: &str) -> Self {
+    Self {
+      code: code.to_string(),
+      ..Default::default()
+    }
+  }
+
+  pub fn esm(mut self, bindings: &[&str]) -> Self {
+    self.esm = true;
+    self.bindings = bindings.iter().map(|binding| binding.to_string()).collect();
+    self
+  }
+
+  /// Statically imports `names` from `specifier`.
+  pub fn importing(mut self, specifier: &str, names: &[&str]) -> Self {
+    self
+      .imports
+      .insert(specifier.to_string(), names.iter().map(|name| name.to_string()).collect());
+    self.with_dependency(specifier, ResolveKind::Import)
+  }
+
+  /// Re-exports `names` from `specifier`, or everything when `None`.
+  pub fn reexporting(mut self, specifier: &str, names: Option<&[&str]>) -> Self {
+    let names = names.map(|names| names.iter().map(|name| name.to_string()).collect());
+    self.reexports.insert(specifier.to_string(), names);
+    self.with_dependency(specifier, ResolveKind::Import)
+  }
+
+  pub fn side_effect_free(mut self) -> Self {
+    self.side_effect_free = true;
+    self
+  }
+
+  /// Whether the module exports `name` other than through `export *`.
+  fn declares(&self, name: &String) -> bool {
+    self.bindings.contains(name)
+      || self
+        .reexports
+        .values()
+        .any(|names| names.as_ref().is_some_and(|names| names.contains(name)))
+  }
+
+  pub fn with_dependency(mut self, specifier: &str, kind: ResolveKind) -> Self {
+    self.dependencies.push(ModuleDependency {
+      specifier: specifier.to_string(),
+      kind,
+    });
+    self
+  }
+}
+
+impl Module for TestModule {
+  fn render(&self) -> String {
+    self.code.clone()
+  }
+
+  fn dependencies(&mut self) -> Vec<ModuleDependency> {
+    self.dependencies.clone()
+  }
+
+  fn imported_names(&self, dependency: &ModuleDependency, used_exports: &UsedExports) -> ImportedNames {
+    if let Some(names) = self.imports.get(&dependency.specifier) {
+      return ImportedNames::Names(names.clone());
+    }
+    match (self.reexports.get(&dependency.specifier), used_exports) {
+      (None, _) | (Some(None), UsedExports::All) => ImportedNames::All,
+      (Some(Some(names)), _) => ImportedNames::Names(
+        names
+          .iter()
+          .filter(|name| used_exports.is_used(name))
+          .cloned()
+          .collect(),
+      ),
+      (Some(None), UsedExports::Names(used)) => ImportedNames::Names(
+        used
+          .iter()
+          .filter(|name| !self.declares(name))
+          .cloned()
+          .collect(),
+      ),
+    }
+  }
+
+  fn has_side_effects(&self) -> bool {
+    !self.side_effect_free
+  }
+
+  fn is_esm(&self) -> bool {
+    self.esm
+  }
+
+  fn top_level_bindings(&self) -> Vec<String> {
+    self.bindings.clone()
+  }
+
+  fn export_bindings(&self) -> HashMap<String, String> {
+    self
+      .bindings
+      .iter()
+      .map(|binding| (binding.clone(), binding.clone()))
+      .collect()
+  }
+
+  /// Renders the code followed by the scope, e.g. `a [x=x_1] /b.js:{y=y} used=x`.
+  fn render_concatenated(&self, scope: &ConcatenationScope) -> String {
+    let mut bindings = scope
+      .bindings
+      .iter()
+      .map(|(binding, name)| format!("{}={}", binding, name))
+      .collect::<Vec<_>>();
+    bindings.sort();
+    let mut imports = scope
+      .imports
+      .iter()
+      .map(|(specifier, imported)| match imported {
+        ImportedModule::Concatenated(exports) => {
+          let mut exports = exports
+            .iter()
+            .map(|(export, name)| format!("{}={}", export, name))
+            .collect::<Vec<_>>();
+          exports.sort();
+          format!("{}:{{{}}}", specifier, exports.join(","))
+        }
+        ImportedModule::External(uri) => format!("{}:external {}", specifier, uri),
+      })
+      .collect::<Vec<_>>();
+    imports.sort();
+    format!(
+      "{} [{}] {} used={}",
+      self.code,
+      bindings.join(","),
+      imports.join(" "),
+      used_names(scope.used_exports)
+    )
+  }
+}
+
+/// Builds a compilation out of `modules` keyed by uri, their specifiers being the
+/// uris of the modules they import.
+pub fn build_compilation(entries: &[&str], modules: Vec<(&str, TestModule)>) -> Compilation {
+  let mut compilation = Compilation::default();
+  for entry in entries {
+    compilation.add_entry(entry.to_string(), EntryItem::from(entry.to_string()));
+    compilation.module_graph.add_dependency(
+      Dependency {
+        importer: None,
+        specifier: entry.to_string(),
+        kind: ResolveKind::Import,
+      },
+      entry.to_string(),
+    );
+  }
+  for (uri, mut module) in modules {
+    let dependencies = module
+      .dependencies()
+      .into_iter()
+      .map(|dep| Dependency {
+        importer: Some(uri.to_string()),
+        specifier: dep.specifier,
+        kind: dep.kind,
+      })
+      .collect::<Vec<_>>();
+    for dep in &dependencies {
+      compilation
+        .module_graph
+        .add_dependency(dep.clone(), dep.specifier.clone());
+    }
+    compilation.module_graph.add_module(ModuleGraphModule::new(
+      Box::new(module),
+      uri.to_string(),
+      SourceType::Js,
+      dependencies,
+    ));
+  }
+  compilation.calc_exec_order();
+  compilation.calc_used_exports();
+  compilation
+}
+
+/// Parses js files made of `import <specifier>`, `import() <specifier>` and
+/// `require <specifier>` lines into `TestModule`s.
+#[derive(Debug)]
+pub struct TestParserPlugin;
+
+#[async_trait]
+impl Plugin for TestParserPlugin {
+  fn register_parse_module(&self, _ctx: PluginContext) -> Option<Vec<SourceType>> {
+    Some(vec![SourceType::Js])
+  }
+
+  async fn parse_module(
+    &self,
+    _ctx: PluginContext<&mut JobContext>,
+    args: ParseModuleArgs<'_>,
+  ) -> anyhow::Result<BoxModule> {
+    let code = args.source.try_into_string()?;
+    let module = code
+      .lines()
+      .filter_map(|line| {
+        let (keyword, specifier) = line.trim().split_once(' ')?;
+        let kind = match keyword {
+          "import" => ResolveKind::Import,
+          "import()" => ResolveKind::DynamicImport,
+          "require" => ResolveKind::Require,
+          _ => return None,
+        };
+        Some((specifier.trim(), kind))
+      })
+      .fold(TestModule::new(&code), |module, (specifier, kind)| {
+        module.with_dependency(specifier, kind)
+      });
+    Ok(Box::new(module))
+  }
+}
+
+/// Writes `files` to a new directory under the temp dir and returns its path.
+pub fn write_fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+  let root = std::env::temp_dir().join(format!("rspack_core_{}_{}", name, std::process::id()));
+  let _ = std::fs::remove_dir_all(&root);
+  for (path, content) in files {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+  }
+  root
+}
+
+/// `*` for `UsedExports::All`, otherwise the sorted names.
+pub fn used_names(used_exports: &UsedExports) -> String {
+  match used_exports {
+    UsedExports::All => String::from("*"),
+    UsedExports::Names(names) => {
+      let mut names = names.iter().cloned().collect::<Vec<_>>();
+      names.sort();
+      names.join(",")
+    }
+  }
+}
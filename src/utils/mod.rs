@@ -22,4 +22,10 @@ pub fn ext_by_module_uri(root: &str, uri: &str) -> String {
     });
   name.push_str(&ext);
   name  
-}
\ No newline at end of file
+}
+
+/// Hash of `content` as hex, xxh3 gives the same result across platforms and
+/// toolchain versions.
+pub fn hash_content(content: &[u8]) -> String {
+  format!("{:016x}", xxhash_rust::xxh3::xxh3_64(content))
+}